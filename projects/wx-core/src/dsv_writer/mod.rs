@@ -1,26 +1,4 @@
-use std::{borrow::Cow, fmt::Display, marker::PhantomData};
-
-/// DSV 编码器
-pub struct DsvWriter {
-    file_name: Cow<'static, str>,
-}
-impl DsvWriter {
-    /// 新建一个 DSV 编码器
-    pub fn new<T>(name: T) -> Self
-    where
-        T: Into<Cow<'static, str>>,
-    {
-        Self { file_name: name.into() }
-    }
-    // pub fn make_attachment<S>(&self, s: S) -> Attachment<Body>
-    // where
-    //     S: Stream<Item = Result<CsvLine, std::io::Error>> + Send + 'static,
-    // {
-    //     let sync = SyncStream::new(s).map_ok(|s| s.as_frame());
-    //     let body = Body::from(BoxBody::new(http_body_util::StreamBody::new(sync)));
-    //     Attachment::new(body).filename(self.file_name.to_string())
-    // }
-}
+use std::{fmt::Display, marker::PhantomData};
 
 pub struct CsvConfig;
 
@@ -65,7 +43,7 @@ impl<F: DsvFormat> DsvLine<F> {
         self.push_str(&format!("{}", value))
     }
     pub fn push_utf8_bom(&mut self) {
-        self.buffer.push_str("\u{feff}");
+        self.buffer.push('\u{feff}');
     }
     pub fn push_str(&mut self, string: &str) {
         if self.needs_quote(string) {
@@ -82,7 +60,7 @@ impl<F: DsvFormat> DsvLine<F> {
             self.buffer.push(F::QUOTE);
         }
         else {
-            self.buffer.push_str(&string);
+            self.buffer.push_str(string);
         }
         self.buffer.push(F::DELIMITER);
     }
@@ -1,7 +1,8 @@
 use super::*;
-use aes::cipher::{InvalidLength, block_padding::UnpadError};
+use aes::cipher::{InvalidLength, block_padding::UnpadError, inout::PadError};
 use lz4_flex::block::DecompressError;
 use std::{
+    any::Any,
     array::TryFromSliceError,
     num::ParseIntError,
     path::{Path, StripPrefixError},
@@ -29,6 +30,11 @@ impl From<InvalidLength> for WxError {
         WxError { kind: Box::new(WxErrorKind::DecodeError { algorithm: "InvalidLength", message: error.to_string() }) }
     }
 }
+impl From<PadError> for WxError {
+    fn from(error: PadError) -> Self {
+        WxError { kind: Box::new(WxErrorKind::DecodeError { algorithm: "Pad", message: error.to_string() }) }
+    }
+}
 impl From<UnpadError> for WxError {
     fn from(error: UnpadError) -> Self {
         WxError { kind: Box::new(WxErrorKind::DecodeError { algorithm: "Unpad", message: error.to_string() }) }
@@ -77,13 +83,33 @@ impl From<sqlx::Error> for WxError {
     }
 }
 impl WxError {
+    /// 错误的类型
+    pub fn kind(&self) -> &WxErrorKind {
+        &self.kind
+    }
+    /// 自定义报错
     pub fn custom(message: impl ToString) -> WxError {
         WxError { kind: Box::new(WxErrorKind::Custom { message: message.to_string() }) }
     }
+    /// 当前版本不支持读取该字段
     pub fn unsupported_offset(version: &str, field: &str) -> WxError {
         WxError { kind: Box::new(WxErrorKind::UnsupportedOffset { version: version.to_string(), field: field.to_string() }) }
     }
+    /// 秘钥无法解密该路径下的数据库
     pub fn invalid_key(key: [u8; 32], path: &Path) -> WxError {
         WxError { kind: Box::new(WxErrorKind::InvalidKey { key, path: path.to_owned() }) }
     }
+    /// 第 `page` 页的 hmac 校验失败
+    pub fn invalid_hmac(page: u32) -> WxError {
+        WxError { kind: Box::new(WxErrorKind::InvalidHmac { page }) }
+    }
+    /// 工作线程崩溃, `payload` 为 [`JoinHandle::join`](std::thread::JoinHandle::join) 返回的错误
+    pub fn thread_panic(payload: &(dyn Any + Send)) -> WxError {
+        let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+            (Some(s), _) => s.to_string(),
+            (_, Some(s)) => s.clone(),
+            _ => "未知错误".to_string(),
+        };
+        WxError { kind: Box::new(WxErrorKind::ThreadPanic { message }) }
+    }
 }
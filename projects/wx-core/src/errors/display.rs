@@ -19,9 +19,12 @@ impl Display for WxErrorKind {
         match self {
             Self::Custom { message } => write!(f, "{}", message),
             Self::UnsupportedOffset { version, field } => write!(f, "微信版本 {} 不支持读取 {}", version, field),
+            #[cfg(windows)]
             Self::Window { error } => write!(f, "系统错误: {}", error),
             Self::InvalidKey { key: _, path } => write!(f, "秘钥不匹配, 无法解密 {}", path.display()),
+            Self::InvalidHmac { page } => write!(f, "第 {} 页 hmac 校验失败", page),
             Self::DatabaseError { error } => write!(f, "数据库错误: {}", error),
+            Self::ThreadPanic { message } => write!(f, "线程崩溃: {}", message),
             Self::DecodeError { algorithm, message } => write!(f, "{} 解码错误: {}", algorithm, message),
         }
     }
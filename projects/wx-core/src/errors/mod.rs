@@ -38,9 +38,21 @@ pub enum WxErrorKind {
         /// 待解密的文件夹
         path: PathBuf,
     },
+    /// hmac 校验失败
+    InvalidHmac {
+        /// 校验失败的页号, 从 1 开始
+        page: u32,
+    },
+    /// 数据库错误
     DatabaseError {
+        /// 错误对象
         error: sqlx::Error,
     },
+    /// 工作线程崩溃
+    ThreadPanic {
+        /// 崩溃时的信息
+        message: String,
+    },
     /// 解码失败
     DecodeError {
         /// 解码算法
@@ -1,12 +1,17 @@
-use crate::{WxError, WxResult};
-use std::{collections::BTreeMap, fs::read_dir, path::PathBuf};
+use crate::{WxAccounts, WxError, WxResult};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
+/// 获取微信聊天记录的文件夹, 未指定时使用用户修改过的存储位置, 或者系统文档文件夹下的 `WeChat Files`
 pub fn get_wechat_path(given: &Option<String>) -> WxResult<PathBuf> {
     let path = match given {
         Some(wechat_path) => PathBuf::from(wechat_path),
-        None => match dirs::document_dir() {
-            Some(s) => s.join("WeChat Files"),
-            None => Err(WxError::custom("fail to get document directory"))?,
+        None => match (custom_wechat_path(), dirs::document_dir()) {
+            (Some(s), _) => s,
+            (None, Some(s)) => s.join("WeChat Files"),
+            (None, None) => Err(WxError::custom("fail to get document directory"))?,
         },
     };
     if path.exists() {
@@ -20,23 +25,21 @@ pub fn get_wechat_path(given: &Option<String>) -> WxResult<PathBuf> {
     Ok(path)
 }
 
-pub async fn read_database(wechat_path: &Option<String>) -> WxResult<BTreeMap<String, PathBuf>> {
-    let mut map = BTreeMap::new();
-    let wechat_path_buf = get_wechat_path(wechat_path)?;
-
-    for entity in read_dir(wechat_path_buf)? {
-        let entity = entity?;
-        if entity.file_name() == "All Users" || entity.file_name() == "Applet" || entity.file_name() == "WMPF" {
-            continue;
-        }
-        if entity.file_type()?.is_dir() {
-            match entity.file_name().into_string() {
-                Ok(o) => {
-                    map.insert(o, entity.path().join("Msg"));
-                }
-                Err(_) => {}
-            }
-        }
+/// 用户修改过的存储位置, 保存在 `%APPDATA%/Tencent/WeChat/All Users/config/3ebffe94.ini`
+///
+/// 内容为 `MyDocument:` 时表示使用默认的文档文件夹
+pub fn custom_wechat_path() -> Option<PathBuf> {
+    let ini = dirs::config_dir()?.join("Tencent/WeChat/All Users/config/3ebffe94.ini");
+    let content = std::fs::read_to_string(ini).ok()?;
+    let content = content.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    match content {
+        "" | "MyDocument:" => None,
+        path => Some(Path::new(path).join("WeChat Files")),
     }
-    Ok(map)
+}
+
+/// 列出微信文件夹下所有账号的 `Msg` 文件夹, 没有 `Msg` 文件夹的账号会被跳过
+pub async fn read_database(wechat_path: &Option<String>) -> WxResult<BTreeMap<String, PathBuf>> {
+    let accounts = WxAccounts::discover(wechat_path, &[])?;
+    Ok(accounts.accounts.into_iter().filter_map(|account| Some((account.wxid, account.msg_path?))).collect())
 }
@@ -4,17 +4,35 @@
 #![doc(html_logo_url = "https://raw.githubusercontent.com/oovm/shape-rs/dev/projects/images/Trapezohedron.svg")]
 #![doc(html_favicon_url = "https://raw.githubusercontent.com/oovm/shape-rs/dev/projects/images/Trapezohedron.svg")]
 
+mod dsv_writer;
 mod errors;
+/// 辅助函数
 pub mod helpers;
 mod orm_types;
+mod protobuf;
+mod wx_account;
 mod wx_decrypt;
+mod wx_encrypt;
 mod wx_export;
+mod wx_salvage;
 mod wx_scanner;
-mod dsv_writer;
+mod wx_snapshot;
 
 pub use crate::{
     errors::{WxError, WxErrorKind, WxResult},
-    wx_decrypt::WxDecryptor,
+    orm_types::{
+        AppAttachment, AppMsg, BytesExtra, ChannelInfo, ChatRoom, ChatRoomMember, Contact, ContactKind, ForwardRecord, Message,
+        MiniProgramInfo, MusicInfo, ReferMsg,
+    },
+    protobuf::{ProtoField, ProtoReader, WireValue},
+    wx_account::{WxAccount, WxAccounts},
+    wx_decrypt::{
+        CipherProfile, DecryptedReader, HmacAlgorithm, WxDecryptFile, WxDecryptProgress, WxDecryptReport, WxDecryptStatus,
+        WxDecryptor, WxKeyChecker,
+    },
+    wx_encrypt::WxEncryptor,
     wx_export::WxExport,
-    wx_scanner::{WeChatProfile, WxScanner},
+    wx_salvage::WxSalvage,
+    wx_scanner::{WeChatProfile, WxKeyLocation, WxMemory, WxMemoryDump, WxScanner, save_offset_map},
+    wx_snapshot::{WxSnapshot, WxSnapshotFile},
 };
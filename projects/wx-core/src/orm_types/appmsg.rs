@@ -0,0 +1,156 @@
+use super::xml::{tag_inner, tag_text};
+
+/// type 49 消息中的 `<appmsg>`, 来自解压后的 `CompressContent` 或者 `StrContent`
+///
+/// 只读取导出需要的标签, 缺少的标签为空
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AppMsg {
+    /// `<type>`, 和消息的 `SubType` 相同
+    pub kind: i32,
+    /// 标题, 文件消息中为文件名
+    pub title: String,
+    /// 描述
+    pub description: String,
+    /// 链接
+    pub url: String,
+    /// 来源的名称, 例如公众号或者小程序的名称
+    pub source_name: String,
+    /// 文件的附件信息
+    pub attachment: Option<AppAttachment>,
+    /// 回复时引用的消息
+    pub refer: Option<ReferMsg>,
+    /// 分享的小程序
+    pub mini_program: Option<MiniProgramInfo>,
+    /// 分享的音乐
+    pub music: Option<MusicInfo>,
+    /// 分享的视频号动态
+    pub channel: Option<ChannelInfo>,
+}
+
+/// `<appattach>`, 文件的附件信息
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AppAttachment {
+    /// 文件大小
+    pub size: u64,
+    /// 扩展名, 不包括 `.`
+    pub extension: String,
+}
+
+/// `<refermsg>`, 回复时引用的消息
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReferMsg {
+    /// 被引用的消息在服务器上的 id, 对应 `MSG.MsgSvrID`
+    pub svrid: u64,
+    /// 被引用的消息的 `Type`
+    pub kind: i32,
+    /// 被引用的消息的发送者 wxid
+    pub sender: String,
+    /// 被引用的消息的发送者昵称
+    pub display_name: String,
+    /// 被引用的消息的内容, 不是文本时为原始 XML
+    pub content: String,
+}
+
+/// `<weappinfo>`, 分享的小程序
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MiniProgramInfo {
+    /// 小程序的名称
+    pub app_name: String,
+    /// 小程序的原始 id, 例如 `gh_xxx@app`
+    pub username: String,
+    /// 打开的页面
+    pub page: String,
+}
+
+/// 分享的音乐
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MusicInfo {
+    /// 歌手, 来自 `<des>`
+    pub singer: String,
+    /// 音频的链接
+    pub data_url: String,
+}
+
+/// `<finderFeed>`, 分享的视频号动态
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChannelInfo {
+    /// 视频号的名称
+    pub nickname: String,
+    /// 动态的描述
+    pub description: String,
+    /// 动态的 id
+    pub object_id: String,
+}
+
+impl AppMsg {
+    /// 解析 `<msg><appmsg>...</appmsg></msg>`, 没有 `<appmsg>` 时按整个 XML 读取
+    pub fn parse(xml: &str) -> Self {
+        let body = tag_inner(xml, "appmsg").unwrap_or(xml);
+        let text = |xml: &str, tag: &str| tag_text(xml, tag).unwrap_or_default();
+        let number = |xml: &str, tag: &str| text(xml, tag).trim().parse::<u64>().unwrap_or_default();
+        let kind = number(body, "type") as i32;
+        let mut msg = Self {
+            kind,
+            title: text(body, "title"),
+            description: text(body, "des"),
+            url: text(body, "url"),
+            source_name: tag_text(body, "sourcedisplayname")
+                .filter(|s| !s.is_empty())
+                .or_else(|| tag_text(xml, "appname"))
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        if let Some(attach) = tag_inner(body, "appattach").filter(|_| kind == 6) {
+            msg.attachment = Some(AppAttachment { size: number(attach, "totallen"), extension: text(attach, "fileext") });
+        }
+        if let Some(refer) = tag_inner(body, "refermsg") {
+            msg.refer = Some(ReferMsg {
+                svrid: number(refer, "svrid"),
+                kind: number(refer, "type") as i32,
+                sender: text(refer, "fromusr"),
+                display_name: text(refer, "displayname"),
+                content: text(refer, "content"),
+            });
+        }
+        if matches!(kind, 33 | 36) {
+            let weapp = tag_inner(body, "weappinfo").unwrap_or_default();
+            msg.mini_program = Some(MiniProgramInfo {
+                app_name: msg.source_name.clone(),
+                username: text(weapp, "username"),
+                page: text(weapp, "pagepath"),
+            });
+        }
+        if matches!(kind, 3 | 76) {
+            msg.music = Some(MusicInfo { singer: msg.description.clone(), data_url: text(body, "dataurl") });
+        }
+        if let Some(feed) = tag_inner(body, "finderFeed") {
+            msg.channel = Some(ChannelInfo {
+                nickname: text(feed, "nickname"),
+                description: text(feed, "desc"),
+                object_id: text(feed, "objectId"),
+            });
+        }
+        msg
+    }
+}
+
+impl ReferMsg {
+    /// 被引用的消息的简短描述, 非文本消息只显示类型
+    pub fn summary(&self) -> String {
+        match self.kind {
+            3 => "[图片]".to_string(),
+            34 => "[语音]".to_string(),
+            43 => "[视频]".to_string(),
+            47 => "[表情]".to_string(),
+            48 => "[位置]".to_string(),
+            49 => {
+                let app = AppMsg::parse(&self.content);
+                match app.title.is_empty() {
+                    true => "[链接]".to_string(),
+                    false => app.title,
+                }
+            }
+            _ => self.content.clone(),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{ProtoReader, WxResult};
+
+/// 群聊中消息的发送者
+const SENDER: u32 = 1;
+/// 缩略图的路径, 图片和视频消息都有
+const THUMBNAIL: u32 = 3;
+/// 原图, 视频或者文件的路径
+const RESOURCE: u32 = 4;
+
+/// `MSG` 表中的 `BytesExtra` 列
+///
+/// 字段 3 重复出现, 每个都包含类型 (字段 1) 和字符串 (字段 2), 其他字段会被忽略
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BytesExtra {
+    /// 按出现顺序排列的类型和字符串
+    pub entries: Vec<(u32, String)>,
+}
+
+impl BytesExtra {
+    /// 解码 `BytesExtra`, 空的数据返回空的结果
+    pub fn parse(data: &[u8]) -> WxResult<Self> {
+        let mut entries = vec![];
+        for field in ProtoReader::new(data) {
+            let field = field?;
+            let Some(inner) = field.value.as_message().filter(|_| field.number == 3)
+            else {
+                continue;
+            };
+            let (mut kind, mut text) = (0, None);
+            for field in inner {
+                let field = field?;
+                match field.number {
+                    1 => kind = field.value.as_u64().unwrap_or_default() as u32,
+                    2 => text = field.value.as_str(),
+                    _ => {}
+                }
+            }
+            if let Some(text) = text {
+                entries.push((kind, text.to_string()));
+            }
+        }
+        Ok(Self { entries })
+    }
+    /// 指定类型的第一个字符串
+    pub fn get(&self, kind: u32) -> Option<&str> {
+        self.entries.iter().find(|(k, _)| *k == kind).map(|(_, v)| v.as_str())
+    }
+    /// 群聊中消息的发送者 wxid, 私聊和自己发送的消息中没有
+    pub fn sender(&self) -> Option<&str> {
+        self.get(SENDER).filter(|s| !s.is_empty())
+    }
+    /// 缩略图的路径, 相对于 `WeChat Files` 文件夹
+    pub fn thumbnail(&self) -> Option<&str> {
+        self.get(THUMBNAIL).filter(|s| !s.is_empty())
+    }
+    /// 原图, 视频或者文件的路径, 相对于 `WeChat Files` 文件夹
+    pub fn resource(&self) -> Option<&str> {
+        self.get(RESOURCE).filter(|s| !s.is_empty())
+    }
+    /// 所有本地资源的路径, 包括缩略图, 原图对应的 `.dat`, 视频和文件
+    pub fn resource_paths(&self) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|(k, v)| (*k == THUMBNAIL || *k == RESOURCE) && !v.is_empty())
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+}
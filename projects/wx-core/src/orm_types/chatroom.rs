@@ -0,0 +1,162 @@
+use crate::{ProtoReader, WxExport, WxResult, dsv_writer::CsvLine};
+use serde::Serialize;
+use sqlx::{Error, FromRow, Row, sqlite::SqliteRow};
+use tokio::{fs::File, io::AsyncWriteExt};
+use tracing::debug;
+
+/// `MicroMsg.db` 中的一个群聊
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ChatRoom {
+    /// 群聊的 id, 例如 `123@chatroom`
+    pub wxid: String,
+    /// 群名称, 来自 `Contact.NickName`
+    pub name: String,
+    /// 群主的 wxid, 来自 `ChatRoom.Reserved2`
+    pub owner: String,
+    /// 群公告, 来自 `ChatRoomInfo`
+    pub announcement: String,
+    /// 最后编辑群公告的成员 wxid
+    pub announcement_editor: String,
+    /// 群成员, 按 `RoomData` 中的顺序排列
+    pub members: Vec<ChatRoomMember>,
+}
+
+/// 群聊中的一个成员
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ChatRoomMember {
+    /// 成员的 wxid
+    pub wxid: String,
+    /// 在群里设置的昵称, 没有设置时为空
+    pub display_name: String,
+}
+
+impl ChatRoom {
+    /// 解码 `ChatRoom.RoomData` 中的成员
+    ///
+    /// 字段 1 重复出现, 每个都包含 wxid (字段 1) 和群昵称 (字段 2), 没有设置群昵称时为空
+    pub fn read_room_data(&mut self, data: &[u8]) -> WxResult<()> {
+        let mut members = vec![];
+        for field in ProtoReader::new(data) {
+            let field = field?;
+            let Some(inner) = field.value.as_message().filter(|_| field.number == 1)
+            else {
+                continue;
+            };
+            let (mut wxid, mut display_name) = (None, None);
+            for field in inner {
+                let field = field?;
+                match field.number {
+                    1 => wxid = field.value.as_str(),
+                    2 => display_name = field.value.as_str(),
+                    _ => {}
+                }
+            }
+            if let Some(wxid) = wxid {
+                members.push(ChatRoomMember {
+                    wxid: wxid.to_string(),
+                    display_name: display_name.unwrap_or_default().to_string(),
+                });
+            }
+        }
+        self.members = members;
+        Ok(())
+    }
+    /// 成员的群昵称, 不是成员或者没有设置时返回 `None`
+    pub fn display_name(&self, wxid: &str) -> Option<&str> {
+        self.members.iter().find(|m| m.wxid == wxid).map(|m| m.display_name.as_str()).filter(|s| !s.is_empty())
+    }
+    /// 成员数量
+    pub fn member_count(&self) -> usize {
+        self.members.len()
+    }
+    /// 设置了群昵称的成员数量
+    pub fn named_member_count(&self) -> usize {
+        self.members.iter().filter(|m| !m.display_name.is_empty()).count()
+    }
+}
+
+impl<'a> FromRow<'a, SqliteRow> for ChatRoom {
+    fn from_row(row: &'a SqliteRow) -> Result<Self, Error> {
+        let text =
+            |column: &str| -> Result<String, Error> { Ok(row.try_get::<Option<String>, _>(column)?.unwrap_or_default()) };
+        let mut room = ChatRoom {
+            wxid: row.try_get("ChatRoomName")?,
+            name: text("NickName")?,
+            owner: text("Reserved2")?,
+            announcement: text("Announcement")?,
+            announcement_editor: text("AnnouncementEditor")?,
+            members: vec![],
+        };
+        let data: Option<Vec<u8>> = row.try_get("RoomData")?;
+        if let Err(e) = room.read_room_data(data.as_deref().unwrap_or_default()) {
+            debug!("无法解码群成员: {}, {}", room.wxid, e);
+        }
+        // 没有 RoomData 时使用 `^G` 分隔的成员列表, 这里没有群昵称
+        if room.members.is_empty() {
+            room.members = text("UserNameList")?
+                .split("^G")
+                .filter(|s| !s.is_empty())
+                .map(|wxid| ChatRoomMember { wxid: wxid.to_string(), ..Default::default() })
+                .collect();
+        }
+        Ok(room)
+    }
+}
+
+/// 导出到 JSON 时附带成员数量
+#[derive(Serialize)]
+struct ChatRoomSummary<'a> {
+    #[serde(flatten)]
+    room: &'a ChatRoom,
+    member_count: usize,
+    named_member_count: usize,
+}
+
+impl WxExport {
+    /// 读取 `MicroMsg.db` 中的所有群聊
+    pub async fn read_chatrooms(&self) -> WxResult<Vec<ChatRoom>> {
+        let db = self.connect_micro_msg().await?;
+        Ok(sqlx::query_as::<_, ChatRoom>(include_str!("get_chatroom.sql")).fetch_all(&db).await?)
+    }
+    /// 导出群聊到 `ChatRoom.csv` 和 `ChatRoom.json`, CSV 中每个成员一行
+    pub async fn export_chatrooms(&self) -> WxResult<()> {
+        let rooms = self.read_chatrooms().await?;
+        let output = self.output_path.as_ref().unwrap_or(&self.db);
+        let mut file = File::create(output.join("ChatRoom.csv")).await?;
+        let mut line = CsvLine::new();
+        // UTF8 HEAD for Excel
+        line.push_utf8_bom();
+        for title in ["群聊", "群名称", "群主", "成员数", "群公告", "成员", "群昵称"] {
+            line.push_str(title);
+        }
+        file.write_all(line.finish().as_bytes()).await?;
+        for room in &rooms {
+            for member in &room.members {
+                let mut line = CsvLine::new();
+                line.push_str(&room.wxid);
+                line.push_str(&room.name);
+                line.push_str(&room.owner);
+                line.push_display(room.member_count());
+                line.push_str(&room.announcement);
+                line.push_str(&member.wxid);
+                line.push_str(&member.display_name);
+                file.write_all(line.finish().as_bytes()).await?;
+            }
+        }
+        file.flush().await?;
+        let summaries: Vec<_> = rooms
+            .iter()
+            .map(|room| ChatRoomSummary {
+                room,
+                member_count: room.member_count(),
+                named_member_count: room.named_member_count(),
+            })
+            .collect();
+        let mut buffer = vec![];
+        let mut serializer =
+            serde_json::Serializer::with_formatter(&mut buffer, serde_json::ser::PrettyFormatter::with_indent(b"    "));
+        summaries.serialize(&mut serializer)?;
+        tokio::fs::write(output.join("ChatRoom.json"), buffer).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use crate::{WxExport, WxResult, dsv_writer::CsvLine};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+use tokio::{fs::File, io::AsyncWriteExt};
+use tracing::debug;
+
+/// 性别, 1 为男, 2 为女
+const GENDER: [u8; 4] = [0x74, 0x75, 0x2C, 0x06];
+/// 个性签名
+const SIGNATURE: [u8; 4] = [0x46, 0xCF, 0x10, 0xC4];
+/// 国家或地区
+const COUNTRY: [u8; 4] = [0xA4, 0xD9, 0x02, 0x4A];
+/// 省份
+const PROVINCE: [u8; 4] = [0xE2, 0xEA, 0xA8, 0xD1];
+/// 城市
+const CITY: [u8; 4] = [0x1D, 0x02, 0x5B, 0xBF];
+
+/// `MicroMsg.db` 中的一个联系人
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Contact {
+    /// 微信内部的用户名, 例如 `wxid_xxx` 或者 `123@chatroom`
+    pub wxid: String,
+    /// 微信号, 没有设置时为空
+    pub alias: String,
+    /// 备注
+    pub remark: String,
+    /// 昵称
+    pub nickname: String,
+    /// 标签的名称, 来自 `ContactLabel`
+    pub labels: Vec<String>,
+    /// 联系人的类型
+    pub kind: ContactKind,
+    /// 性别, 1 为男, 2 为女, 0 为未知, 来自 `ExtraBuf`
+    pub gender: u32,
+    /// 地区, 依次为国家, 省份和城市, 来自 `ExtraBuf`
+    pub region: String,
+    /// 个性签名, 来自 `ExtraBuf`
+    pub signature: String,
+    /// 头像的链接, 来自 `ContactHeadImgUrl`
+    pub head_image_url: String,
+}
+
+/// 联系人的类型
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContactKind {
+    /// 好友
+    Friend,
+    /// 群聊
+    ChatRoom,
+    /// 公众号
+    OfficialAccount,
+    /// 企业微信联系人
+    Enterprise,
+    /// 不是好友, 例如群聊中的陌生人
+    #[default]
+    Other,
+}
+
+impl ContactKind {
+    /// 由 `UserName`, `Type` 和 `VerifyFlag` 判断类型
+    pub fn classify(wxid: &str, type_id: i64, verify_flag: i64) -> Self {
+        if wxid.ends_with("@chatroom") {
+            Self::ChatRoom
+        }
+        else if wxid.ends_with("@openim") {
+            Self::Enterprise
+        }
+        else if wxid.starts_with("gh_") || verify_flag != 0 {
+            Self::OfficialAccount
+        }
+        else if type_id & 1 != 0 {
+            Self::Friend
+        }
+        else {
+            Self::Other
+        }
+    }
+}
+
+/// 导出到 CSV 的名称
+impl Display for ContactKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Friend => "好友",
+            Self::ChatRoom => "群聊",
+            Self::OfficialAccount => "公众号",
+            Self::Enterprise => "企业微信",
+            Self::Other => "其他",
+        })
+    }
+}
+
+impl Contact {
+    /// 解码 `ExtraBuf` 中的性别, 地区和个性签名
+    ///
+    /// 每个字段由 4 字节的键, 1 字节的类型和值组成, 类型 `0x04` 为 4 字节整数,
+    /// `0x17` 和 `0x18` 为带 4 字节长度的 UTF-8 和 UTF-16 字符串
+    pub fn read_extra_buf(&mut self, data: &[u8]) {
+        self.gender = match extra_value(data, GENDER) {
+            Some(ExtraValue::Int(v)) => v,
+            _ => 0,
+        };
+        self.signature = extra_text(data, SIGNATURE);
+        let region: Vec<String> =
+            [COUNTRY, PROVINCE, CITY].into_iter().map(|key| extra_text(data, key)).filter(|s| !s.is_empty()).collect();
+        self.region = region.join(" ");
+    }
+}
+
+enum ExtraValue {
+    Int(u32),
+    Text(String),
+}
+
+fn extra_text(data: &[u8], key: [u8; 4]) -> String {
+    match extra_value(data, key) {
+        Some(ExtraValue::Text(s)) => s,
+        _ => String::new(),
+    }
+}
+
+fn extra_value(data: &[u8], key: [u8; 4]) -> Option<ExtraValue> {
+    let offset = data.windows(4).position(|w| w == key)? + 4;
+    let (&kind, rest) = data.get(offset..)?.split_first()?;
+    let int = u32::from_le_bytes(rest.get(..4)?.try_into().ok()?);
+    let text = match kind {
+        0x04 => return Some(ExtraValue::Int(int)),
+        0x17 => String::from_utf8_lossy(rest.get(4..4 + int as usize)?).to_string(),
+        0x18 => {
+            let units: Vec<u16> =
+                rest.get(4..4 + int as usize)?.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+    Some(ExtraValue::Text(text.trim_end_matches('\0').to_string()))
+}
+
+impl WxExport {
+    /// 读取 `MicroMsg.db` 中的所有联系人
+    pub async fn read_contacts(&self) -> WxResult<Vec<Contact>> {
+        let db = self.connect_micro_msg().await?;
+        let labels: HashMap<String, String> =
+            match sqlx::query_as::<_, (i64, String)>("SELECT LabelId, LabelName FROM ContactLabel").fetch_all(&db).await {
+                Ok(rows) => rows.into_iter().map(|(id, name)| (id.to_string(), name)).collect(),
+                Err(e) => {
+                    debug!("无法读取联系人标签: {}", e);
+                    HashMap::new()
+                }
+            };
+        let head_images: HashMap<String, String> = match sqlx::query_as::<_, (String, Option<String>, Option<String>)>(
+            "SELECT usrName, bigHeadImgUrl, smallHeadImgUrl FROM ContactHeadImgUrl",
+        )
+        .fetch_all(&db)
+        .await
+        {
+            Ok(rows) => rows
+                .into_iter()
+                .filter_map(|(wxid, big, small)| {
+                    big.filter(|s| !s.is_empty()).or(small).filter(|s| !s.is_empty()).map(|url| (wxid, url))
+                })
+                .collect(),
+            Err(e) => {
+                debug!("无法读取联系人头像: {}", e);
+                HashMap::new()
+            }
+        };
+        let rows = sqlx::query_as::<
+            _,
+            (String, Option<String>, Option<String>, Option<String>, Option<String>, Option<i64>, Option<i64>, Option<Vec<u8>>),
+        >("SELECT UserName, Alias, Remark, NickName, LabelIDList, Type, VerifyFlag, ExtraBuf FROM Contact")
+        .fetch_all(&db)
+        .await?;
+        let mut contacts = Vec::with_capacity(rows.len());
+        for (wxid, alias, remark, nickname, label_ids, type_id, verify_flag, extra) in rows {
+            let mut contact = Contact {
+                kind: ContactKind::classify(&wxid, type_id.unwrap_or_default(), verify_flag.unwrap_or_default()),
+                labels: label_ids.unwrap_or_default().split(',').filter_map(|id| labels.get(id.trim())).cloned().collect(),
+                head_image_url: head_images.get(&wxid).cloned().unwrap_or_default(),
+                alias: alias.unwrap_or_default(),
+                remark: remark.unwrap_or_default(),
+                nickname: nickname.unwrap_or_default(),
+                wxid,
+                ..Default::default()
+            };
+            contact.read_extra_buf(extra.as_deref().unwrap_or_default());
+            contacts.push(contact);
+        }
+        Ok(contacts)
+    }
+    /// 导出联系人到 `Contact.csv` 和 `Contact.json`
+    pub async fn export_contacts(&self) -> WxResult<()> {
+        let contacts = self.read_contacts().await?;
+        let output = self.output_path.as_ref().unwrap_or(&self.db);
+        let mut file = File::create(output.join("Contact.csv")).await?;
+        let mut line = CsvLine::new();
+        // UTF8 HEAD for Excel
+        line.push_utf8_bom();
+        for title in ["wxid", "微信号", "备注", "昵称", "标签", "类型", "性别", "地区", "个性签名", "头像"]
+        {
+            line.push_str(title);
+        }
+        file.write_all(line.finish().as_bytes()).await?;
+        for contact in &contacts {
+            let mut line = CsvLine::new();
+            line.push_str(&contact.wxid);
+            line.push_str(&contact.alias);
+            line.push_str(&contact.remark);
+            line.push_str(&contact.nickname);
+            line.push_str(&contact.labels.join(";"));
+            line.push_display(contact.kind);
+            line.push_str(match contact.gender {
+                1 => "男",
+                2 => "女",
+                _ => "",
+            });
+            line.push_str(&contact.region);
+            line.push_str(&contact.signature);
+            line.push_str(&contact.head_image_url);
+            file.write_all(line.finish().as_bytes()).await?;
+        }
+        file.flush().await?;
+        let mut buffer = vec![];
+        let mut serializer =
+            serde_json::Serializer::with_formatter(&mut buffer, serde_json::ser::PrettyFormatter::with_indent(b"    "));
+        contacts.serialize(&mut serializer)?;
+        tokio::fs::write(output.join("Contact.json"), buffer).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use super::ChatRoom;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use tracing::debug;
+
+/// 群聊中发送者的显示名称, 依次使用群昵称, 联系人备注和昵称
+#[derive(Debug, Default)]
+pub(crate) struct DisplayNames {
+    /// 群聊和群成员, 来自 `ChatRoom.RoomData`
+    rooms: HashMap<String, ChatRoom>,
+    /// 联系人备注, 来自 `Contact.Remark`
+    remarks: HashMap<String, String>,
+    /// 联系人昵称, 来自 `Contact.NickName`
+    nicknames: HashMap<String, String>,
+}
+
+impl DisplayNames {
+    /// 从附加的 `MicroMsg` 中读取, 缺少的表会被跳过
+    pub async fn load(db: &SqlitePool) -> Self {
+        let mut names = Self::default();
+        match sqlx::query_as::<_, (String, Option<String>, Option<String>)>(
+            "SELECT UserName, Remark, NickName FROM MicroMsg.Contact",
+        )
+        .fetch_all(db)
+        .await
+        {
+            Ok(rows) => {
+                for (wxid, remark, nickname) in rows {
+                    if let Some(remark) = remark.filter(|s| !s.is_empty()) {
+                        names.remarks.insert(wxid.clone(), remark);
+                    }
+                    if let Some(nickname) = nickname.filter(|s| !s.is_empty()) {
+                        names.nicknames.insert(wxid, nickname);
+                    }
+                }
+            }
+            Err(e) => debug!("无法读取联系人: {}", e),
+        }
+        match sqlx::query_as::<_, (String, Option<Vec<u8>>)>("SELECT ChatRoomName, RoomData FROM MicroMsg.ChatRoom")
+            .fetch_all(db)
+            .await
+        {
+            Ok(rows) => {
+                for (wxid, data) in rows {
+                    let mut room = ChatRoom { wxid, ..Default::default() };
+                    match room.read_room_data(data.as_deref().unwrap_or_default()) {
+                        Ok(()) => {
+                            names.rooms.insert(room.wxid.clone(), room);
+                        }
+                        Err(e) => debug!("无法解码群成员: {}, {}", room.wxid, e),
+                    }
+                }
+            }
+            Err(e) => debug!("无法读取群聊: {}", e),
+        }
+        names
+    }
+    /// 发送者的显示名称, 都没有时使用 wxid
+    pub fn resolve<'a>(&'a self, room: &str, wxid: &'a str) -> &'a str {
+        self.rooms
+            .get(room)
+            .and_then(|room| room.display_name(wxid))
+            .or_else(|| self.remarks.get(wxid).map(|s| s.as_str()))
+            .or_else(|| self.nicknames.get(wxid).map(|s| s.as_str()))
+            .unwrap_or(wxid)
+    }
+}
@@ -0,0 +1,88 @@
+use super::xml::{tag_attribute, tag_elements, tag_text};
+use std::fmt::{Display, Formatter};
+
+/// 合并转发的聊天记录中的一条消息, 来自 `<recorditem>` 中的 `<dataitem>`
+///
+/// 转发的聊天记录里可以再包含聊天记录, 这时 `children` 中是嵌套的消息
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ForwardRecord {
+    /// `datatype` 属性, 1 为文本, 2 为图片, 17 为聊天记录等
+    pub datatype: i32,
+    /// 原始发送者的昵称
+    pub sender: String,
+    /// 原始发送的时间, 例如 `2023-10-1 12:00`
+    pub time: String,
+    /// 标题, 链接, 文件和聊天记录才有
+    pub title: String,
+    /// 内容, 文本消息为原文, 其他消息为微信生成的描述
+    pub content: String,
+    /// 嵌套的聊天记录
+    pub children: Vec<ForwardRecord>,
+}
+
+impl ForwardRecord {
+    /// 解析 `<recordinfo>` 中 `<datalist>` 的所有消息, 包括嵌套的聊天记录
+    pub fn parse_list(record_xml: &str) -> Vec<Self> {
+        let info = first_inner(record_xml, "recordinfo").unwrap_or(record_xml);
+        let list = first_inner(info, "datalist").unwrap_or_default();
+        tag_elements(list, "dataitem").into_iter().map(|(open, item)| Self::parse_item(open, item)).collect()
+    }
+    fn parse_item(open: &str, item: &str) -> Self {
+        let nested = first_inner(item, "recordxml");
+        // 只从自己的标签中读取, 不要读到嵌套的聊天记录里
+        let own = match nested {
+            Some(nested) => item.replacen(nested, "", 1),
+            None => item.to_string(),
+        };
+        let text = |tag: &str| tag_text(&own, tag).unwrap_or_default();
+        Self {
+            datatype: tag_attribute(open, "dataitem", "datatype").and_then(|s| s.parse().ok()).unwrap_or_default(),
+            sender: text("sourcename"),
+            time: text("sourcetime"),
+            title: text("datatitle"),
+            content: text("datadesc"),
+            children: nested.map(Self::parse_list).unwrap_or_default(),
+        }
+    }
+    /// 导出时显示的内容, 非文本消息带有类型前缀
+    pub fn summary(&self) -> String {
+        let prefix = match self.datatype {
+            1 => return self.content.clone(),
+            2 => "[图片]",
+            3 => "[语音]",
+            4 => "[视频]",
+            5 => "[链接]",
+            6 => "[位置]",
+            8 => "[文件]",
+            16 => "[名片]",
+            17 => "[聊天记录]",
+            19 => "[小程序]",
+            22 => "[视频号]",
+            _ => "[消息]",
+        };
+        match (self.title.is_empty(), self.content.is_empty()) {
+            (false, _) => format!("{} {}", prefix, self.title),
+            (true, false) => format!("{} {}", prefix, self.content),
+            (true, true) => prefix.to_string(),
+        }
+    }
+    /// 写入这一条和嵌套的消息, 每层缩进两个空格
+    pub(crate) fn write_tree(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        write!(f, "\n{:width$}{} {}: {}", "", self.sender, self.time, self.summary(), width = depth * 2)?;
+        for child in &self.children {
+            child.write_tree(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// 导出时的缩进块, 从第一层开始缩进
+impl Display for ForwardRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.write_tree(f, 1)
+    }
+}
+
+fn first_inner<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    tag_elements(xml, tag).into_iter().next().map(|(_, inner)| inner)
+}
@@ -0,0 +1,4 @@
+select room.*, info.Announcement, info.AnnouncementEditor, contact.NickName
+from ChatRoom room
+         left join ChatRoomInfo info on info.ChatRoomName = room.ChatRoomName
+         left join Contact contact on contact.UserName = room.ChatRoomName
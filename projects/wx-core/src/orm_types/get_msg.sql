@@ -1,4 +1,3 @@
-ATTACH DATABASE ?1 AS MicroMsg;
 select message.*, room.strNickName
 --        n.UsrName
 from MSG message
@@ -0,0 +1,315 @@
+use super::{
+    AppMsg, BytesExtra, ForwardRecord, ReferMsg,
+    xml::{decompress_text, tag_attribute, tag_text},
+};
+use std::fmt::{Display, Formatter};
+
+/// 一条消息的内容, 由 `Type`, `SubType`, `StrContent`, `CompressContent` 和 `BytesExtra` 解析而来
+///
+/// 无法识别或者解析失败的消息会保留为 [`Message::Unknown`], 不会被丢弃
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// 纯文本
+    Text {
+        /// 文本内容
+        text: String,
+    },
+    /// 带有引用的文本消息
+    ///
+    /// 这种类型下 `StrContent` 为空，发送和引用的内容均在 `CompressContent` 中
+    Reply {
+        /// 回复的内容
+        text: String,
+        /// 被引用的消息
+        quoted: ReferMsg,
+    },
+    /// 图片
+    Image {
+        /// 图片的 md5
+        md5: Option<String>,
+        /// 本地保存的路径, 来自 `BytesExtra`
+        path: Option<String>,
+    },
+    /// 语音
+    Voice {
+        /// 时长, 单位为毫秒
+        duration_ms: u64,
+    },
+    /// 视频
+    Video {
+        /// 时长, 单位为秒
+        duration_secs: u64,
+        /// 视频的 md5
+        md5: Option<String>,
+        /// 本地保存的路径, 来自 `BytesExtra`
+        path: Option<String>,
+    },
+    /// 表情, 包括第三方开发的动画表情和用户上传的 GIF 表情
+    Emoji {
+        /// 表情的 md5
+        md5: String,
+        /// CDN 链接
+        url: Option<String>,
+    },
+    /// 二进制文件
+    File {
+        /// 文件名
+        name: String,
+        /// 文件大小
+        size: u64,
+        /// 本地保存的路径, 来自 `BytesExtra`
+        path: Option<String>,
+    },
+    /// 分享的链接, 以及其他无法细分的 appmsg
+    Link {
+        /// appmsg 中的类型
+        kind: i32,
+        /// 标题
+        title: String,
+        /// 描述
+        description: String,
+        /// 链接
+        url: String,
+    },
+    /// 分享的小程序
+    MiniProgram {
+        /// 小程序的名称
+        app_name: String,
+        /// 卡片的标题
+        title: String,
+        /// 打开的页面
+        page: String,
+    },
+    /// 分享的音乐
+    Music {
+        /// 歌名
+        title: String,
+        /// 歌手
+        singer: String,
+        /// 链接
+        url: String,
+    },
+    /// 分享的视频号动态
+    Channel {
+        /// 视频号的名称
+        nickname: String,
+        /// 动态的描述
+        description: String,
+    },
+    /// 位置
+    Location {
+        /// 纬度
+        latitude: f64,
+        /// 经度
+        longitude: f64,
+        /// 地址
+        label: String,
+        /// 地点名称
+        poi_name: String,
+    },
+    /// 名片
+    ContactCard {
+        /// 用户名
+        username: String,
+        /// 昵称
+        nickname: String,
+    },
+    /// 转账
+    Transfer {
+        /// 金额, 例如 `￥0.01`
+        amount: String,
+        /// 转账说明
+        memo: String,
+        /// 1 为发起转账, 3 为已收款, 4 为已退还
+        pay_subtype: i32,
+    },
+    /// 红包
+    RedPacket {
+        /// 红包的祝福语
+        title: String,
+    },
+    /// 合并转发的聊天记录
+    ForwardRecords {
+        /// 标题, 例如 `群聊的聊天记录`
+        title: String,
+        /// 预览的内容
+        description: String,
+        /// 转发的消息, 可能嵌套了其他聊天记录
+        records: Vec<ForwardRecord>,
+    },
+    /// 撤回消息的提示
+    Revoke {
+        /// 提示内容
+        text: String,
+    },
+    /// 拍一拍
+    Pat {
+        /// 提示内容
+        text: String,
+    },
+    /// 语音或视频通话
+    VoIP {
+        /// 通话的结果, 例如 `通话时长 00:12`
+        text: String,
+    },
+    /// 系统通知, 居中出现的那种灰色文字, 包括邀请入群等
+    System {
+        /// 通知内容
+        text: String,
+    },
+    /// 未知类型
+    Unknown {
+        /// 类别 id
+        type_id: i32,
+        /// 子类 id
+        sub_id: i32,
+        /// `StrContent` 或者解压后的 `CompressContent`
+        content: String,
+    },
+}
+
+impl Message {
+    /// 解析一条消息, 参数依次为 `Type`, `SubType`, `StrContent`, `CompressContent` 和解码后的 `BytesExtra`
+    pub fn parse(type_id: i32, sub_id: i32, content: &str, compress: &[u8], extra: &BytesExtra) -> Self {
+        let appmsg = || decompress_text(compress).ok().filter(|s| !s.is_empty()).unwrap_or_else(|| content.to_string());
+        let text = |xml: &str, tag: &str| tag_text(xml, tag).unwrap_or_default();
+        let number = |value: Option<String>| value.and_then(|s| s.trim().parse::<u64>().ok()).unwrap_or_default();
+        match (type_id, sub_id) {
+            (1, _) => Self::Text { text: content.to_string() },
+            (3, _) => Self::Image {
+                md5: tag_attribute(content, "img", "md5"),
+                path: extra.resource().or(extra.thumbnail()).map(String::from),
+            },
+            (34, _) => Self::Voice { duration_ms: number(tag_attribute(content, "voicemsg", "voicelength")) },
+            (42, _) => Self::ContactCard {
+                username: tag_attribute(content, "msg", "username").unwrap_or_default(),
+                nickname: tag_attribute(content, "msg", "nickname").unwrap_or_default(),
+            },
+            (43, _) => Self::Video {
+                duration_secs: number(tag_attribute(content, "videomsg", "playlength")),
+                md5: tag_attribute(content, "videomsg", "md5"),
+                path: extra.resource().map(String::from),
+            },
+            (47, _) => Self::Emoji {
+                md5: tag_attribute(content, "emoji", "md5").unwrap_or_default(),
+                url: tag_attribute(content, "emoji", "cdnurl"),
+            },
+            (48, _) => Self::Location {
+                latitude: tag_attribute(content, "location", "x").and_then(|s| s.parse().ok()).unwrap_or_default(),
+                longitude: tag_attribute(content, "location", "y").and_then(|s| s.parse().ok()).unwrap_or_default(),
+                label: tag_attribute(content, "location", "label").unwrap_or_default(),
+                poi_name: tag_attribute(content, "location", "poiname").unwrap_or_default(),
+            },
+            (49, _) => {
+                let xml = appmsg();
+                let app = AppMsg::parse(&xml);
+                match (sub_id, app) {
+                    (6, app) => Self::File {
+                        name: app.title,
+                        size: app.attachment.map(|a| a.size).unwrap_or_default(),
+                        path: extra.resource().map(String::from),
+                    },
+                    (8, _) => Self::Emoji { md5: text(&xml, "emoticonmd5"), url: tag_text(&xml, "cdnurl") },
+                    (19, app) => Self::ForwardRecords {
+                        title: app.title,
+                        description: app.description,
+                        records: ForwardRecord::parse_list(&text(&xml, "recorditem")),
+                    },
+                    (_, AppMsg { title, mini_program: Some(mini), .. }) => {
+                        Self::MiniProgram { app_name: mini.app_name, title, page: mini.page }
+                    }
+                    (_, AppMsg { title, music: Some(music), url, .. }) => Self::Music { title, singer: music.singer, url },
+                    (_, AppMsg { channel: Some(channel), .. }) => {
+                        Self::Channel { nickname: channel.nickname, description: channel.description }
+                    }
+                    (57, AppMsg { title, refer: Some(quoted), .. }) => Self::Reply { text: title, quoted },
+                    (2000, _) => Self::Transfer {
+                        amount: text(&xml, "feedesc"),
+                        memo: text(&xml, "pay_memo"),
+                        pay_subtype: number(tag_text(&xml, "paysubtype")) as i32,
+                    },
+                    (2001, _) => Self::RedPacket { title: text(&xml, "sendertitle") },
+                    (kind, app) => Self::Link { kind, title: app.title, description: app.description, url: app.url },
+                }
+            }
+            (50, _) => Self::VoIP { text: tag_text(content, "msg").unwrap_or_else(|| content.to_string()) },
+            (10000, 4) => Self::Pat { text: tag_text(content, "template").unwrap_or_else(|| content.to_string()) },
+            (10000, _) if content.contains("撤回了一条消息") => Self::Revoke { text: content.to_string() },
+            (10000, _) => Self::System { text: content.to_string() },
+            (10002, _) => match tag_attribute(content, "sysmsg", "type").as_deref() {
+                Some("revokemsg") => Self::Revoke { text: text(content, "replacemsg") },
+                Some("pat") => Self::Pat { text: text(content, "template") },
+                _ => Self::System { text: content.to_string() },
+            },
+            (type_id, sub_id) => Self::Unknown { type_id, sub_id, content: appmsg() },
+        }
+    }
+    /// 导出时使用的类型名称
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Text { .. } => "Text",
+            Self::Reply { .. } => "Reply",
+            Self::Image { .. } => "Image",
+            Self::Voice { .. } => "Voice",
+            Self::Video { .. } => "Video",
+            Self::Emoji { .. } => "Emoji",
+            Self::File { .. } => "File",
+            Self::Link { .. } => "Link",
+            Self::MiniProgram { .. } => "MiniProgram",
+            Self::Music { .. } => "Music",
+            Self::Channel { .. } => "Channel",
+            Self::Location { .. } => "Location",
+            Self::ContactCard { .. } => "ContactCard",
+            Self::Transfer { .. } => "Transfer",
+            Self::RedPacket { .. } => "RedPacket",
+            Self::ForwardRecords { .. } => "ForwardRecords",
+            Self::Revoke { .. } => "Revoke",
+            Self::Pat { .. } => "Pat",
+            Self::VoIP { .. } => "VoIP",
+            Self::System { .. } => "System",
+            Self::Unknown { .. } => "Unknown",
+        }
+    }
+}
+
+/// 导出到 CSV 的内容
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text { text } => f.write_str(text),
+            Self::Reply { text, quoted } => {
+                let summary = quoted.summary();
+                match summary.chars().count() > 20 {
+                    true => {
+                        write!(f, "回复 {}「{}…」: {}", quoted.display_name, summary.chars().take(20).collect::<String>(), text)
+                    }
+                    false => write!(f, "回复 {}「{}」: {}", quoted.display_name, summary, text),
+                }
+            }
+            Self::Image { path, .. } => write!(f, "[图片] {}", path.as_deref().unwrap_or_default()),
+            Self::Voice { duration_ms } => write!(f, "[语音] {} 秒", duration_ms.div_ceil(1000)),
+            Self::Video { duration_secs, path, .. } => {
+                write!(f, "[视频] {} 秒 {}", duration_secs, path.as_deref().unwrap_or_default())
+            }
+            Self::Emoji { md5, .. } => write!(f, "[表情] {}", md5),
+            Self::File { name, size, .. } => write!(f, "[文件] {} ({} 字节)", name, size),
+            Self::Link { title, url, .. } => write!(f, "[链接] {} {}", title, url),
+            Self::MiniProgram { app_name, title, .. } => write!(f, "[小程序] {}: {}", app_name, title),
+            Self::Music { title, singer, .. } => write!(f, "[音乐] {} - {}", title, singer),
+            Self::Channel { nickname, description } => write!(f, "[视频号] {}: {}", nickname, description),
+            Self::Location { latitude, longitude, label, poi_name } => {
+                write!(f, "[位置] {} {} ({}, {})", poi_name, label, latitude, longitude)
+            }
+            Self::ContactCard { username, nickname } => write!(f, "[名片] {} ({})", nickname, username),
+            Self::Transfer { amount, memo, .. } => write!(f, "[转账] {} {}", amount, memo),
+            Self::RedPacket { title } => write!(f, "[红包] {}", title),
+            Self::ForwardRecords { title, records, .. } => {
+                write!(f, "[聊天记录] {}", title)?;
+                records.iter().try_for_each(|record| write!(f, "{}", record))
+            }
+            Self::Revoke { text } | Self::Pat { text } | Self::System { text } => f.write_str(text),
+            Self::VoIP { text } => write!(f, "[通话] {}", text),
+            Self::Unknown { content, .. } => f.write_str(content),
+        }
+    }
+}
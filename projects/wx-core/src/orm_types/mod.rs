@@ -1,43 +1,55 @@
-use crate::{
-    WxExport, WxResult,
-    dsv_writer::{CsvLine, },
-};
+use self::display_names::DisplayNames;
+use crate::{WxDecryptor, WxExport, WxResult, dsv_writer::CsvLine, wx_decrypt::memory_pool};
 
 use chrono::{DateTime, Local};
 use futures_util::stream::TryStreamExt;
-use lz4_flex::{ decompress, };
 use sqlx::{
-    Error, FromRow, Row, Sqlite,
+    Error, FromRow, Row, Sqlite, SqlitePool,
     sqlite::{SqlitePoolOptions, SqliteRow},
 };
 use std::{
     fmt::{Debug, Formatter},
-    path::PathBuf,
-
+    path::Path,
+    sync::Arc,
 };
 use tokio::{fs::File, io::AsyncWriteExt};
 
-#[allow(non_snake_case)]
+mod appmsg;
+mod bytes_extra;
+mod chatroom;
+mod contact;
+mod display_names;
+mod forward;
+mod message;
+mod xml;
+
+pub use self::{
+    appmsg::{AppAttachment, AppMsg, ChannelInfo, MiniProgramInfo, MusicInfo, ReferMsg},
+    bytes_extra::BytesExtra,
+    chatroom::{ChatRoom, ChatRoomMember},
+    contact::{Contact, ContactKind},
+    forward::ForwardRecord,
+    message::Message,
+};
+
 struct MessageRow {
-    r#type: MessageType,
+    message: Message,
+    extra: BytesExtra,
+    /// 发送者的 wxid, 自己发送的消息和群里的系统消息为空
+    sender: Option<String>,
     time: DateTime<Local>,
-    message: String,
-    CompressContent: Vec<u8>,
-    extra: Vec<u8>,
     is_sender: bool,
-    // room_id: String,
     room_id: String,
     room_name: String,
 }
 
 impl Debug for MessageRow {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("MessageRaw")
-            .field("type", &self.r#type)
-            .field("time", &self.time)
+        f.debug_struct("MessageRow")
             .field("message", &self.message)
-            .field("CompressContent", &self.CompressContent.len())
-            .field("binary_extra", &self.extra.len())
+            .field("extra", &self.extra)
+            .field("sender", &self.sender)
+            .field("time", &self.time)
             .field("is_sender", &self.is_sender)
             .field("room_id", &self.room_id)
             .field("room_name", &self.room_name)
@@ -45,124 +57,43 @@ impl Debug for MessageRow {
     }
 }
 
-impl MessageRow {
-    pub fn binary_as_string(&self) -> WxResult<String> {
-        let mut decompress = decompress(&self.CompressContent, 0x10004)?;
-        // 移除字符串末尾的 `<NUL>`
-        let tail = decompress.pop();
-        debug_assert_eq!(tail, Some(0x00));
-        Ok(String::from_utf8(decompress)?)
-    }
-    /// 将 `CompressContent` 字段转为 `ReferenceText` 格式
-    pub fn binary_as_message(&self) -> WxResult<String> {
-        let xml = self.binary_as_string()?;
-        Ok(xml)
-    }
-}
-
-#[derive(Debug)]
-pub enum MessageType {
-    /// 纯文本
-    Text,
-    /// 带有引用的文本消息
-    ///
-    /// 这种类型下 `StrContent` 为空，发送和引用的内容均在 `CompressContent` 中
-    TextReference,
-    /// 图片
-    Image,
-    /// 语音
-    Voice,
-    /// 视频
-    Video,
-    /// 动画表情
-    ///
-    /// 第三方开发的表情包
-    Emoji,
-    /// GIF 表情
-    ///
-    /// 用户上传的表情包
-    /// - `CompressContent` 中有 CDN 链接
-    EmojiGif,
-    /// 二进制文件
-    ///
-    /// - `CompressContent` 中有文件名和下载链接
-    /// - `BytesExtra` 中有本地保存的路径
-    File,
-    /// 电话
-    PhoneCall,
-    /// 分享的小程序
-    ///
-    /// - `CompressContent` 中有卡片信息
-    /// - `BytesExtra` 中有封面缓存位置
-    MiniProgram,
-    /// 拍一拍
-    PatFriend,
-    /// 系统通知
-    ///
-    /// 居中出现的那种灰色文字
-    SystemNotice,
-    /// 邀请通知
-    ///
-    /// 特别包含你邀请别人加入群聊
-    SystemInvite,
-    /// 未知类型
-    Unknown {
-        /// 类别 id
-        type_id: i32,
-        /// 子类 id
-        sub_id: i32,
-    },
-}
-
-impl From<(i32, i32)> for MessageType {
-    fn from(value: (i32, i32)) -> Self {
-        match value {
-            (1, 0) => Self::Text,
-            (3, 0) => Self::Image,
-            (34, 0) => Self::Voice,
-            (43, 0) => Self::Video,
-            (47, 0) => Self::Emoji,
-            (49, 6) => Self::File,
-            (49, 8) => Self::EmojiGif,
-            (49, 33) => Self::MiniProgram,
-            (49, 36) => Self::MiniProgram,
-            (49, 57) => Self::TextReference,
-            (50, 0) => Self::PhoneCall,
-            (10000, 0) => Self::SystemNotice,
-            (10000, 8000) => Self::SystemInvite,
-            (x, y) => Self::Unknown { type_id: x, sub_id: y },
-        }
-    }
-}
-
 impl<'a> FromRow<'a, SqliteRow> for MessageRow {
     fn from_row(row: &'a SqliteRow) -> Result<Self, Error> {
-        let time: i32 = row.try_get("CreateTime")?;
+        let time: i64 = row.try_get("CreateTime")?;
         let is_sender: bool = row.try_get("IsSender")?;
-
-        let ty = row.try_get("Type")?;
-        let sub = row.try_get("SubType")?;
-        let kind = (ty, sub).into();
-
-        let message = row.try_get("StrContent")?;
-        let binary: Vec<u8> = row.try_get("CompressContent")?;
-        let binary_extra: Vec<u8> = row.try_get("BytesExtra")?;
-        let user_id = row.try_get("StrTalker")?;
-        let user_name = row.try_get("strNickName")?;
-        // let room_id = row.try_get("UsrName")?;
-
-        let utc_datetime = DateTime::from_timestamp(time as i64, 0).unwrap();
-        let local_datetime: DateTime<Local> = utc_datetime.with_timezone(&Local);
+        let type_id: i32 = row.try_get("Type")?;
+        let sub_id: i32 = row.try_get("SubType")?;
+        let content: Option<String> = row.try_get("StrContent")?;
+        let compress: Option<Vec<u8>> = row.try_get("CompressContent")?;
+        let extra: Option<Vec<u8>> = row.try_get("BytesExtra")?;
+        let room_id: String = row.try_get("StrTalker")?;
+        let room_name: Option<String> = row.try_get("strNickName")?;
+        let extra = BytesExtra::parse(extra.as_deref().unwrap_or_default()).unwrap_or_default();
+        let mut content = content.unwrap_or_default();
+        let sender = match (is_sender, room_id.ends_with("@chatroom")) {
+            (true, _) => None,
+            (false, true) => {
+                // 旧版本的群消息以 `wxid:\n` 开头, 新版本的发送者在 `BytesExtra` 中
+                let prefix = content.split_once(":\n").map(|(wxid, _)| wxid.to_string()).filter(|wxid| {
+                    !wxid.is_empty() && !wxid.contains(char::is_whitespace) && extra.sender().is_none_or(|s| s == wxid)
+                });
+                if let Some(wxid) = prefix.as_ref() {
+                    content.drain(..wxid.len() + 2);
+                }
+                extra.sender().map(String::from).or(prefix)
+            }
+            (false, false) => Some(room_id.clone()),
+        };
+        let message = Message::parse(type_id, sub_id, &content, compress.as_deref().unwrap_or_default(), &extra);
+        let utc_datetime = DateTime::from_timestamp(time, 0).unwrap_or_default();
         Ok(MessageRow {
-            r#type: kind,
             message,
-            time: local_datetime,
+            extra,
+            sender,
+            time: utc_datetime.with_timezone(&Local),
             is_sender,
-            // room_id,
-            room_id: user_id,
-            CompressContent: binary,
-            room_name: user_name,
-            extra: binary_extra,
+            room_id,
+            room_name: room_name.unwrap_or_default(),
         })
     }
 }
@@ -170,54 +101,94 @@ impl<'a> FromRow<'a, SqliteRow> for MessageRow {
 impl WxExport {
     /// 导出消息
     pub async fn export_message(&self) -> WxResult<()> {
-        let mut file = File::create(self.db.join("MSG.csv")).await?;
-        // UTF8 HEAD for Excel
-        file.write_all(&[0xEF, 0xBB, 0xBF]).await?;
+        let mut file = File::create(self.output_path.as_ref().unwrap_or(&self.db).join("MSG.csv")).await?;
         let mut line = CsvLine::new();
+        // UTF8 HEAD for Excel
+        line.push_utf8_bom();
         line.push_str("日期");
         line.push_str("会话");
+        line.push_str("发送者");
         line.push_str("内容");
         line.push_str("类型");
         line.push_str("事件");
         file.write_all(line.finish().as_bytes()).await?;
+        // 加密的 MicroMsg.db 只解密一次, 附加到每个消息数据库上
+        let micro_msg = match self.key {
+            Some(_) => Some(Arc::new(self.decryptor().decrypt_in_memory(Path::new("MicroMsg.db"))?)),
+            None => None,
+        };
+        // 联系人和群成员只读取一次
+        let mut names = None;
         for id in 0..99 {
             let db_path = self.db.join(format!("Multi/MSG{}.db", id));
-            self.export_message_on(db_path, &mut file).await?;
+            if !db_path.exists() {
+                continue;
+            }
+            let db = self.connect(&db_path, micro_msg.clone()).await?;
+            if names.is_none() {
+                names = Some(DisplayNames::load(&db).await);
+            }
+            self.export_message_on(&db, &mut file, names.as_ref().unwrap_or(&DisplayNames::default())).await?;
         }
+        // tokio 的文件在后台写入, 关闭前需要等待写入完成
+        file.flush().await?;
         Ok(())
     }
-    async fn export_message_on(&self, msg: PathBuf, file: &mut File) -> WxResult<()> {
-        let micro_msg = self.db.join("MicroMsg.db");
-        let path = micro_msg.to_str().unwrap_or_default();
-        let db = SqlitePoolOptions::new();
-        let db = db.connect(&msg.to_str().unwrap_or_default()).await?;
-        let mut rows = sqlx::query_as::<Sqlite, MessageRow>(include_str!("get_msg.sql"))
-            .bind(path) //
-            .fetch(&db);
+    /// 打开消息数据库, 并把 `MicroMsg.db` 附加为 `MicroMsg`
+    async fn connect(&self, msg: &Path, micro_msg: Option<Arc<Vec<u8>>>) -> WxResult<SqlitePool> {
+        match micro_msg {
+            Some(micro_msg) => {
+                let data = Arc::new(self.decryptor().decrypt_in_memory(msg)?);
+                memory_pool(data, vec![("MicroMsg".to_string(), micro_msg)]).await
+            }
+            None => {
+                let micro_msg = self.db.join("MicroMsg.db");
+                let db = SqlitePoolOptions::new().max_connections(1);
+                let db = db.connect(msg.to_str().unwrap_or_default()).await?;
+                sqlx::query("ATTACH DATABASE ?1 AS MicroMsg").bind(micro_msg.to_str().unwrap_or_default()).execute(&db).await?;
+                Ok(db)
+            }
+        }
+    }
+    /// 单独打开 `MicroMsg.db`
+    async fn connect_micro_msg(&self) -> WxResult<SqlitePool> {
+        match self.key {
+            Some(_) => {
+                let data = Arc::new(self.decryptor().decrypt_in_memory(Path::new("MicroMsg.db"))?);
+                memory_pool(data, vec![]).await
+            }
+            None => {
+                let micro_msg = self.db.join("MicroMsg.db");
+                let db = SqlitePoolOptions::new().max_connections(1);
+                Ok(db.connect(micro_msg.to_str().unwrap_or_default()).await?)
+            }
+        }
+    }
+    fn decryptor(&self) -> WxDecryptor {
+        WxDecryptor {
+            source_path: self.db.clone(),
+            key: self.key.unwrap_or_default(),
+            need_check_hmac: true,
+            ..Default::default()
+        }
+    }
+    async fn export_message_on(&self, db: &SqlitePool, file: &mut File, names: &DisplayNames) -> WxResult<()> {
+        let mut rows = sqlx::query_as::<Sqlite, MessageRow>(include_str!("get_msg.sql")).fetch(db);
         while let Some(row) = rows.try_next().await? {
             let mut line = CsvLine::new();
             line.push_str(&row.time.format("%Y-%m-%d %H:%M:%S").to_string());
-            line.push_str(&row.room_name);
-
-            match row.r#type {
-                MessageType::Text => {
-                    line.push_str(&row.message);
-                    line.push_str("Text");
-                }
-                MessageType::TextReference => {
-                    line.push_str(&row.binary_as_message().unwrap_or_else(|e| e.to_string()));
-                    line.push_str("TextReference")
-                }
-                MessageType::PatFriend => {
-                    line.push_str(&row.message);
-                    line.push_str("PatFriend")
-                }
-                MessageType::Unknown { type_id, sub_id } => {
-                    line.push_str(&row.message);
-                    line.push_str(&format!("Unknown({type_id},{sub_id})"))
-                }
-                _ => continue,
-            }
+            line.push_str(match row.room_name.is_empty() {
+                true => &row.room_id,
+                false => &row.room_name,
+            });
+            line.push_str(match (row.sender.as_deref(), row.is_sender) {
+                (Some(wxid), _) => names.resolve(&row.room_id, wxid),
+                (None, true) => "我",
+                // 群里的撤回, 邀请等系统消息没有发送者
+                (None, false) => "系统",
+            });
+            line.push_str(&row.message.to_string());
+            line.push_str(row.message.kind());
             if row.is_sender {
                 line.push_str("发送");
             }
@@ -0,0 +1,155 @@
+use crate::WxResult;
+use lz4_flex::{block::DecompressError, decompress};
+
+/// 解压 `CompressContent`, 移除字符串末尾的 `<NUL>`
+///
+/// 数据库中没有保存解压后的长度, 从 64 KB 开始尝试, 每次扩大四倍
+pub(crate) fn decompress_text(data: &[u8]) -> WxResult<String> {
+    let mut size = 0x10004;
+    let mut text = loop {
+        match decompress(data, size) {
+            Ok(o) => break o,
+            Err(DecompressError::OutputTooSmall { .. }) if size < 0x4000000 => size *= 4,
+            Err(e) => Err(e)?,
+        }
+    };
+    while text.last() == Some(&0) {
+        text.pop();
+    }
+    Ok(String::from_utf8(text)?)
+}
+
+/// 第一个 `<tag>` 中的文本, 会去掉 CDATA 并转义实体
+pub(crate) fn tag_text(xml: &str, tag: &str) -> Option<String> {
+    tag_inner(xml, tag).map(|s| unescape(s.trim()))
+}
+
+/// 第一个 `<tag>` 中未经处理的内容, 用于继续读取嵌套的标签
+pub(crate) fn tag_inner<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let (start, open) = find_open(xml, tag)?;
+    if open.ends_with("/>") {
+        return Some("");
+    }
+    let body = &xml[start + open.len()..];
+    let end = body.find(&format!("</{}>", tag))?;
+    Some(&body[..end])
+}
+
+/// 所有同一层级的 `<tag>`, 返回开始标签和未经处理的内容, 同名的标签嵌套时按层级匹配
+pub(crate) fn tag_elements<'a>(xml: &'a str, tag: &str) -> Vec<(&'a str, &'a str)> {
+    let close = format!("</{}>", tag);
+    let mut elements = vec![];
+    let mut offset = 0;
+    while let Some((start, open)) = find_open(&xml[offset..], tag) {
+        let body = offset + start + open.len();
+        if open.ends_with("/>") {
+            elements.push((open, ""));
+            offset = body;
+            continue;
+        }
+        let (mut depth, mut cursor) = (1, body);
+        while depth > 0 {
+            let Some(end) = xml[cursor..].find(&close)
+            else {
+                return elements;
+            };
+            match find_open(&xml[cursor..], tag).filter(|(i, _)| *i < end) {
+                Some((i, open)) => {
+                    if !open.ends_with("/>") {
+                        depth += 1;
+                    }
+                    cursor += i + open.len();
+                }
+                None => {
+                    depth -= 1;
+                    cursor += end + close.len();
+                }
+            }
+        }
+        elements.push((open, &xml[body..cursor - close.len()]));
+        offset = cursor;
+    }
+    elements
+}
+
+/// 第一个 `<tag>` 的属性
+pub(crate) fn tag_attribute(xml: &str, tag: &str, name: &str) -> Option<String> {
+    let (_, open) = find_open(xml, tag)?;
+    let mut rest = open;
+    while let Some(i) = rest.find(name) {
+        let before = rest[..i].chars().next_back();
+        let after = rest[i + name.len()..].trim_start();
+        rest = &rest[i + name.len()..];
+        if !matches!(before, Some(c) if c.is_whitespace()) {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=').map(str::trim_start)
+        else {
+            continue;
+        };
+        let quote = value.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            continue;
+        }
+        let end = value[1..].find(quote)?;
+        return Some(unescape(&value[1..1 + end]));
+    }
+    None
+}
+
+/// 找到 `<tag>` 或者 `<tag ...>`, 返回开始的位置和整个开始标签
+fn find_open<'a>(xml: &'a str, tag: &str) -> Option<(usize, &'a str)> {
+    let pattern = format!("<{}", tag);
+    let mut offset = 0;
+    while let Some(i) = xml[offset..].find(&pattern) {
+        let start = offset + i;
+        let rest = &xml[start + pattern.len()..];
+        if rest.starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace()) {
+            let end = rest.find('>')?;
+            return Some((start, &xml[start..start + pattern.len() + end + 1]));
+        }
+        offset = start + pattern.len();
+    }
+    None
+}
+
+/// 去掉 CDATA, 转义 XML 实体
+pub(crate) fn unescape(text: &str) -> String {
+    if let Some(inner) = text.strip_prefix("<![CDATA[").and_then(|s| s.strip_suffix("]]>")) {
+        return inner.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let Some(end) = rest.find(';')
+        else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity.strip_prefix('#').and_then(|n| n.parse().ok()).and_then(char::from_u32),
+            },
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
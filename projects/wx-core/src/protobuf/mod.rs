@@ -0,0 +1,121 @@
+use crate::{WxError, WxErrorKind, WxResult};
+
+/// protobuf 字段的值, 只按 wire type 区分, 不依赖 `.proto` 定义
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WireValue<'a> {
+    /// wire type 0, 整数, 布尔和枚举
+    Varint(u64),
+    /// wire type 1, `fixed64`, `sfixed64` 和 `double`
+    Fixed64(u64),
+    /// wire type 2, 字符串, 字节和嵌套的消息
+    Bytes(&'a [u8]),
+    /// wire type 5, `fixed32`, `sfixed32` 和 `float`
+    Fixed32(u32),
+}
+
+/// protobuf 中的一个字段
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProtoField<'a> {
+    /// 字段编号
+    pub number: u32,
+    /// 字段的值
+    pub value: WireValue<'a>,
+}
+
+/// 逐个读取 protobuf 字段, 遇到损坏的数据时返回错误并停止
+#[derive(Clone, Debug)]
+pub struct ProtoReader<'a> {
+    data: &'a [u8],
+    failed: bool,
+}
+
+impl<'a> WireValue<'a> {
+    /// 整数的值
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Varint(v) | Self::Fixed64(v) => Some(*v),
+            Self::Fixed32(v) => Some(*v as u64),
+            Self::Bytes(_) => None,
+        }
+    }
+    /// 字节的值
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            Self::Bytes(v) => Some(v),
+            _ => None,
+        }
+    }
+    /// UTF-8 字符串的值
+    pub fn as_str(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.as_bytes()?).ok()
+    }
+    /// 作为嵌套的消息读取
+    pub fn as_message(&self) -> Option<ProtoReader<'a>> {
+        self.as_bytes().map(ProtoReader::new)
+    }
+}
+
+impl<'a> ProtoReader<'a> {
+    /// 读取编码后的消息
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, failed: false }
+    }
+    /// 读取所有字段, 任何一个字段损坏都会返回错误
+    pub fn fields(data: &'a [u8]) -> WxResult<Vec<ProtoField<'a>>> {
+        Self::new(data).collect()
+    }
+    fn read_field(&mut self) -> WxResult<ProtoField<'a>> {
+        let key = self.read_varint()?;
+        let number = u32::try_from(key >> 3).map_err(|_| decode_error(format!("字段编号过大: {}", key >> 3)))?;
+        if number == 0 {
+            return Err(decode_error("字段编号不能为 0"));
+        }
+        let value = match key & 7 {
+            0 => WireValue::Varint(self.read_varint()?),
+            1 => WireValue::Fixed64(u64::from_le_bytes(self.take(8)?.try_into()?)),
+            2 => {
+                let length = self.read_varint()?;
+                WireValue::Bytes(self.take(usize::try_from(length).unwrap_or(usize::MAX))?)
+            }
+            5 => WireValue::Fixed32(u32::from_le_bytes(self.take(4)?.try_into()?)),
+            n => return Err(decode_error(format!("不支持的 wire type: {}", n))),
+        };
+        Ok(ProtoField { number, value })
+    }
+    fn read_varint(&mut self) -> WxResult<u64> {
+        let mut value = 0u64;
+        for (i, byte) in self.data.iter().enumerate().take(10) {
+            value |= ((byte & 0x7F) as u64) << (7 * i);
+            if byte & 0x80 == 0 {
+                self.data = &self.data[i + 1..];
+                return Ok(value);
+            }
+        }
+        Err(decode_error("varint 不完整"))
+    }
+    fn take(&mut self, length: usize) -> WxResult<&'a [u8]> {
+        if length > self.data.len() {
+            return Err(decode_error(format!("字段长度 {} 超出剩余的 {} 字节", length, self.data.len())));
+        }
+        let (value, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(value)
+    }
+}
+
+impl<'a> Iterator for ProtoReader<'a> {
+    type Item = WxResult<ProtoField<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.data.is_empty() {
+            return None;
+        }
+        let field = self.read_field();
+        self.failed = field.is_err();
+        Some(field)
+    }
+}
+
+fn decode_error(message: impl Into<String>) -> WxError {
+    WxErrorKind::DecodeError { algorithm: "protobuf", message: message.into() }.into()
+}
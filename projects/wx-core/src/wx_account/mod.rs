@@ -0,0 +1,124 @@
+use crate::{
+    WxKeyChecker, WxResult,
+    helpers::{custom_wechat_path, get_wechat_path},
+};
+use std::{ffi::OsStr, fs::read_dir, path::PathBuf};
+use tracing::debug;
+use walkdir::WalkDir;
+
+/// `WeChat Files` 下不属于任何账号的文件夹
+const NOT_ACCOUNTS: [&str; 3] = ["All Users", "Applet", "WMPF"];
+
+/// 微信文件夹中所有的账号
+#[derive(Clone, Debug)]
+pub struct WxAccounts {
+    /// `WeChat Files` 文件夹
+    pub wechat_path: PathBuf,
+    /// 用户在设置中修改过的存储位置, 来自微信的 `config` 文件
+    pub custom_path: Option<PathBuf>,
+    /// 最后登录的账号, 来自 `All Users/config/config.data`
+    pub last_login: Option<String>,
+    /// 找到的账号, 按名称排序
+    pub accounts: Vec<WxAccount>,
+}
+
+/// 一个账号的文件夹
+#[derive(Clone, Debug)]
+pub struct WxAccount {
+    /// 账号文件夹的名称, 一般为 wxid
+    pub wxid: String,
+    /// 账号文件夹
+    pub path: PathBuf,
+    /// 聊天记录所在的 `Msg` 文件夹, 不存在时为空
+    pub msg_path: Option<PathBuf>,
+    /// `Msg` 文件夹下的数据库, 相对路径和文件大小
+    pub databases: Vec<(PathBuf, u64)>,
+    /// 给定的秘钥中是否有能打开 `MicroMsg.db` 的
+    pub key_known: bool,
+    /// 是否为最后登录的账号
+    pub last_login: bool,
+}
+
+impl WxAccounts {
+    /// 扫描微信文件夹, 未指定时使用自定义的存储位置或者文档文件夹下的 `WeChat Files`
+    ///
+    /// `keys` 用于检查每个账号的数据库能否解密, 可以为空
+    pub fn discover(wechat_path: &Option<String>, keys: &[[u8; 32]]) -> WxResult<Self> {
+        let root = get_wechat_path(wechat_path)?;
+        let config = std::fs::read(root.join("All Users/config/config.data")).unwrap_or_default();
+        let mut accounts = vec![];
+        for entity in read_dir(&root)? {
+            let entity = entity?;
+            let Ok(wxid) = entity.file_name().into_string()
+            else {
+                continue;
+            };
+            let path = entity.path();
+            if NOT_ACCOUNTS.contains(&wxid.as_str()) || !entity.file_type()?.is_dir() {
+                continue;
+            }
+            // 账号文件夹下至少有 Msg 或者 config
+            if !path.join("Msg").is_dir() && !path.join("config").is_dir() {
+                debug!("不是账号文件夹, 跳过: {}", path.display());
+                continue;
+            }
+            accounts.push(WxAccount::new(wxid, path, keys));
+        }
+        accounts.sort_by(|a, b| a.wxid.cmp(&b.wxid));
+        let last_login = last_login(&config, &accounts);
+        for account in accounts.iter_mut() {
+            account.last_login = last_login.as_ref() == Some(&account.wxid);
+        }
+        Ok(Self { wechat_path: root, custom_path: custom_wechat_path(), last_login, accounts })
+    }
+}
+
+impl WxAccount {
+    fn new(wxid: String, path: PathBuf, keys: &[[u8; 32]]) -> Self {
+        let msg_path = Some(path.join("Msg")).filter(|p| p.is_dir());
+        let mut databases = vec![];
+        if let Some(msg) = msg_path.as_ref() {
+            for entry in WalkDir::new(msg).sort_by_file_name().into_iter().flatten() {
+                if entry.path().extension() == Some(OsStr::new("db")) {
+                    let relative = entry.path().strip_prefix(msg).unwrap_or(entry.path()).to_path_buf();
+                    databases.push((relative, entry.metadata().map(|m| m.len()).unwrap_or(0)));
+                }
+            }
+        }
+        let key_known = match msg_path.as_ref().filter(|_| !keys.is_empty()) {
+            Some(msg) => match WxKeyChecker::new(&msg.join("MicroMsg.db")) {
+                Ok(checker) => keys.iter().any(|key| checker.check(key)),
+                Err(_) => false,
+            },
+            None => false,
+        };
+        Self { wxid, path, msg_path, databases, key_known, last_login: false }
+    }
+    /// 数据库的总大小
+    pub fn total_size(&self) -> u64 {
+        self.databases.iter().map(|(_, size)| size).sum()
+    }
+}
+
+/// 在 `config.data` 中寻找最后登录的账号
+///
+/// 其中保存了账号文件夹下的路径, 优先匹配已有的账号文件夹, 找不到时使用第一个 `wxid_` 开头的字符串
+fn last_login(config: &[u8], accounts: &[WxAccount]) -> Option<String> {
+    let text = String::from_utf8_lossy(config);
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    let found = accounts
+        .iter()
+        .filter_map(|account| {
+            let (position, _) = text
+                .match_indices(account.wxid.as_str())
+                .find(|(i, wxid)| !text[..*i].ends_with(is_word) && !text[i + wxid.len()..].starts_with(is_word))?;
+            Some((position, &account.wxid))
+        })
+        .min();
+    if let Some((_, wxid)) = found {
+        return Some(wxid.clone());
+    }
+    let start = text.find("wxid_")?;
+    let end = text[start..].find(|c: char| !is_word(c)).map_or(text.len(), |n| start + n);
+    Some(text[start..end].to_string())
+}
@@ -0,0 +1,170 @@
+use super::*;
+use std::collections::BTreeMap;
+
+/// WAL 文件头的魔数, 最低位表示校验和的字节序
+const WAL_MAGIC: u32 = 0x377f0682;
+
+/// WAL 中已提交的帧
+#[derive(Debug, Default)]
+pub(super) struct WalCommits {
+    /// 读取的 WAL 字节数
+    pub bytes_read: u64,
+    /// 每一页最后一次提交的页数据在 WAL 中的位置
+    pub pages: BTreeMap<u32, u64>,
+    /// 最后一次提交后数据库的总页数
+    pub database_pages: u32,
+}
+
+impl WxDecryptor {
+    /// 找出 WAL 中已提交的帧
+    ///
+    /// 遇到盐或校验和不匹配的帧时停止读取, 和 SQLite 的处理方式一致; 最后一次提交之后的帧会被忽略
+    pub(super) fn read_wal_commits(
+        &self,
+        source_file: &Path,
+        file_wal: &Path,
+        page_size: usize,
+        record: &mut WxDecryptFile,
+    ) -> WxResult<WalCommits> {
+        let mut report = |message: String| {
+            warn!("{}", message);
+            record.warnings.push(message);
+        };
+        let mut reader = BufReader::new(File::open(file_wal)?);
+        let mut header = [0u8; 32];
+        let header_len = read_page(&mut reader, &mut header)?;
+        if header_len < 32 {
+            if header_len != 0 {
+                report(format!("{}-wal 文件头不完整, 已忽略", source_file.display()));
+            }
+            return Ok(WalCommits { bytes_read: header_len as u64, ..Default::default() });
+        }
+        if u32::from_be_bytes(header[0..4].try_into()?) & !1 != WAL_MAGIC {
+            report(format!("{}-wal 文件头无效, 已忽略", source_file.display()));
+            return Ok(WalCommits { bytes_read: header_len as u64, ..Default::default() });
+        }
+        let order_byte = header[3];
+        let (mut sum1, mut sum2) = get_check_sum(0, 0, &header[..24], &order_byte)?;
+        if header[24..32] != [sum1.to_be_bytes(), sum2.to_be_bytes()].concat() {
+            report(format!("{}-wal 文件头校验和错误, 已忽略", source_file.display()));
+            return Ok(WalCommits { bytes_read: header_len as u64, ..Default::default() });
+        }
+        let mut bytes_read = header_len as u64;
+        let mut pending = BTreeMap::new();
+        let mut committed = BTreeMap::new();
+        let mut database_pages = 0;
+        let mut frame = vec![0u8; 24 + page_size];
+        loop {
+            let offset = bytes_read;
+            let length = read_page(&mut reader, &mut frame)?;
+            bytes_read += length as u64;
+            if length < frame.len() {
+                if length != 0 {
+                    report(format!("{}-wal 第 {} 字节处的帧不完整, 已忽略", source_file.display(), offset));
+                }
+                break;
+            }
+            let page_index = u32::from_be_bytes(frame[0..4].try_into()?);
+            let commit = u32::from_be_bytes(frame[4..8].try_into()?);
+            if page_index == 0 || frame[8..16] != header[16..24] {
+                report(format!("{}-wal 第 {} 字节处的帧盐不匹配, 停止读取", source_file.display(), offset));
+                break;
+            }
+            (sum1, sum2) = get_check_sum(sum1, sum2, &frame[..8], &order_byte)?;
+            (sum1, sum2) = get_check_sum(sum1, sum2, &frame[24..], &order_byte)?;
+            if frame[16..24] != [sum1.to_be_bytes(), sum2.to_be_bytes()].concat() {
+                report(format!("{}-wal 第 {} 字节处的帧校验和错误, 停止读取", source_file.display(), offset));
+                break;
+            }
+            pending.insert(page_index, offset + 24);
+            if commit != 0 {
+                committed.append(&mut pending);
+                database_pages = commit;
+            }
+        }
+        if !pending.is_empty() {
+            report(format!("{}-wal 中有 {} 页未提交, 已忽略", source_file.display(), pending.len()));
+        }
+        Ok(WalCommits { bytes_read, pages: committed, database_pages })
+    }
+    /// 把 WAL 中已提交的页写入解密后的数据库, 和 `previous` 中 hmac 相同的页已经写入过, 会被跳过
+    ///
+    /// `tags` 为解密结果中每一页的 hmac, 会更新为写入后的状态
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn apply_wal<W: Write + Seek>(
+        &self,
+        file_wal: &Path,
+        out: &mut W,
+        profile: &CipherProfile,
+        byte_key: &[u8],
+        mac_key: Option<&[u8; 32]>,
+        commits: &WalCommits,
+        tags: &mut Vec<String>,
+        previous: &[String],
+        record: &mut WxDecryptFile,
+    ) -> WxResult<()> {
+        if commits.pages.is_empty() {
+            return Ok(());
+        }
+        tags.resize(commits.database_pages as usize, String::new());
+        let mut wal = File::open(file_wal)?;
+        let mut page = vec![0u8; profile.page_size];
+        let mut decrypted = Vec::with_capacity(profile.page_size);
+        for (&page_index, &offset) in &commits.pages {
+            wal.seek(SeekFrom::Start(offset))?;
+            wal.read_exact(&mut page)?;
+            // 超出最后一次提交的总页数的页会被截断
+            let slot = page_index as usize - 1;
+            if slot >= tags.len() {
+                continue;
+            }
+            let tag = page_tag(profile, &page);
+            if previous.get(slot) == Some(&tag) {
+                tags[slot] = tag;
+                continue;
+            }
+            decrypted.clear();
+            if let Err(e) = decrypt_data(profile, page_index, &page, byte_key, &mut decrypted, mac_key) {
+                // 抢救模式下保留数据库中的旧版本, 并清除标记, 下次增量解密时重新处理这一页
+                if !self.salvage {
+                    return Err(e);
+                }
+                tags[slot].clear();
+                warn!("{} 中第 {} 页的帧解密失败, 已跳过: {}", file_wal.display(), page_index, e);
+                continue;
+            }
+            out.seek(SeekFrom::Start(slot as u64 * profile.page_size as u64))?;
+            out.write_all(&decrypted)?;
+            tags[slot] = tag;
+            record.pages_written += 1;
+        }
+        out.flush()?;
+        trace!("合并成功: {}", file_wal.display());
+        Ok(())
+    }
+    /// 把明文 WAL 中已提交的页原样写入未加密的数据库
+    pub(super) fn apply_plain_wal(
+        &self,
+        file_wal: &Path,
+        data: &mut Vec<u8>,
+        page_size: usize,
+        commits: &WalCommits,
+    ) -> WxResult<()> {
+        if commits.pages.is_empty() {
+            return Ok(());
+        }
+        data.resize(commits.database_pages as usize * page_size, 0);
+        let mut wal = File::open(file_wal)?;
+        for (&page_index, &offset) in &commits.pages {
+            // 超出最后一次提交的总页数的页会被截断
+            let Some(page) = data.get_mut((page_index as usize - 1) * page_size..page_index as usize * page_size)
+            else {
+                continue;
+            };
+            wal.seek(SeekFrom::Start(offset))?;
+            wal.read_exact(page)?;
+        }
+        trace!("合并成功: {}", file_wal.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use super::*;
+use sha2::Sha512;
+
+/// hmac 使用的哈希算法
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HmacAlgorithm {
+    /// HMAC-SHA1, 微信 3.x 使用
+    Sha1,
+    /// HMAC-SHA512, 微信 4.x 使用
+    Sha512,
+}
+
+/// 数据库的加密参数
+///
+/// 每页末尾保留 `reserve` 个字节, 依次存放 16 字节的 IV 和 hmac, 第一页的前 16 个字节是盐
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CipherProfile {
+    /// 页大小
+    pub page_size: usize,
+    /// 每页末尾保留的字节数
+    pub reserve: usize,
+    /// 派生页秘钥时 PBKDF2 的迭代次数
+    pub kdf_iterations: u32,
+    /// hmac 和 PBKDF2 使用的哈希算法
+    pub hmac: HmacAlgorithm,
+}
+
+impl CipherProfile {
+    /// 微信 3.x, 即 SQLCipher 3 的默认参数
+    pub const WECHAT_V3: Self = Self { page_size: 4096, reserve: 48, kdf_iterations: 64000, hmac: HmacAlgorithm::Sha1 };
+    /// 微信 4.x, 即 SQLCipher 4 的默认参数
+    pub const WECHAT_V4: Self = Self { page_size: 4096, reserve: 80, kdf_iterations: 256000, hmac: HmacAlgorithm::Sha512 };
+    /// 所有内置的加密参数
+    pub const BUILTIN: [Self; 2] = [Self::WECHAT_V3, Self::WECHAT_V4];
+
+    /// 用秘钥逐个尝试内置参数, 返回能通过第一页 hmac 校验的参数
+    pub fn detect(key: &[u8], first_page: &[u8]) -> Option<Self> {
+        Self::BUILTIN.into_iter().find(|profile| matches!(profile.check_key(key, first_page), Ok(true)))
+    }
+    /// 指定了参数时只校验该参数, 否则逐个尝试内置参数, 秘钥不匹配时返回空
+    pub fn select(cipher: Option<Self>, key: &[u8], first_page: &[u8]) -> WxResult<Option<Self>> {
+        match cipher {
+            Some(s) => {
+                s.validate()?;
+                Ok(matches!(s.check_key(key, first_page), Ok(true)).then_some(s))
+            }
+            None => Ok(Self::detect(key, first_page)),
+        }
+    }
+    /// 用第一页的 hmac 校验秘钥
+    pub fn check_key(&self, key: &[u8], first_page: &[u8]) -> WxResult<bool> {
+        self.validate()?;
+        if first_page.len() < self.page_size {
+            return Ok(false);
+        }
+        let (_, mac_key) = self.derive_keys(key, &first_page[0..16])?;
+        self.check_page(&first_page[16..self.page_size], &mac_key, 1)
+    }
+    /// 检查参数是否可用, 保留区必须能放下 IV 和 hmac, 剩下的密文按 AES 块对齐
+    pub fn validate(&self) -> WxResult<()> {
+        if self.reserve < 16 + self.hmac_size() {
+            return Err(WxError::custom(format!("保留区 {} 字节放不下 IV 和 {} 字节的 hmac", self.reserve, self.hmac_size())));
+        }
+        if self.reserve + 16 > self.page_size || !(self.page_size - self.reserve).is_multiple_of(16) {
+            return Err(WxError::custom(format!("页大小 {} 和保留区 {} 字节不匹配", self.page_size, self.reserve)));
+        }
+        Ok(())
+    }
+    /// hmac 的长度
+    pub fn hmac_size(&self) -> usize {
+        match self.hmac {
+            HmacAlgorithm::Sha1 => 20,
+            HmacAlgorithm::Sha512 => 64,
+        }
+    }
+    /// 从秘钥和盐派生出页秘钥和 hmac 秘钥
+    pub(crate) fn derive_keys(&self, key: &[u8], salt: &[u8]) -> WxResult<([u8; 32], [u8; 32])> {
+        let mac_salt = salt.iter().map(|i| i ^ 58).collect::<Vec<_>>();
+        let mut byte_key = [0u8; 32];
+        let mut mac_key = [0u8; 32];
+        match self.hmac {
+            HmacAlgorithm::Sha1 => {
+                pbkdf2::pbkdf2::<Hmac<Sha1>>(key, salt, self.kdf_iterations, &mut byte_key)?;
+                pbkdf2::pbkdf2::<Hmac<Sha1>>(&byte_key, &mac_salt, 2, &mut mac_key)?;
+            }
+            HmacAlgorithm::Sha512 => {
+                pbkdf2::pbkdf2::<Hmac<Sha512>>(key, salt, self.kdf_iterations, &mut byte_key)?;
+                pbkdf2::pbkdf2::<Hmac<Sha512>>(&byte_key, &mac_salt, 2, &mut mac_key)?;
+            }
+        }
+        Ok((byte_key, mac_key))
+    }
+    /// 计算一页的 hmac, `data` 为密文和 IV
+    pub(crate) fn page_hmac(&self, mac_key: &[u8], data: &[u8], index: u32) -> WxResult<Vec<u8>> {
+        Ok(match self.hmac {
+            HmacAlgorithm::Sha1 => {
+                let mut hash_mac = Hmac::<Sha1>::new_from_slice(mac_key)?;
+                hash_mac.update(data);
+                hash_mac.update(&index.to_le_bytes());
+                hash_mac.finalize().into_bytes().to_vec()
+            }
+            HmacAlgorithm::Sha512 => {
+                let mut hash_mac = Hmac::<Sha512>::new_from_slice(mac_key)?;
+                hash_mac.update(data);
+                hash_mac.update(&index.to_le_bytes());
+                hash_mac.finalize().into_bytes().to_vec()
+            }
+        })
+    }
+    /// 校验一页的 hmac, 第一页需要去掉开头的盐
+    pub(crate) fn check_page(&self, page: &[u8], mac_key: &[u8], index: u32) -> WxResult<bool> {
+        self.validate()?;
+        if page.len() < self.reserve {
+            return Ok(false);
+        }
+        let offset = page.len() - self.reserve + 16;
+        let r = self.page_hmac(mac_key, &page[..offset], index)?;
+        Ok(r[..] == page[offset..offset + self.hmac_size()])
+    }
+}
@@ -0,0 +1,91 @@
+use super::*;
+use sqlx::{
+    Executor, SqlitePool,
+    sqlite::{SqliteOwnedBuf, SqlitePoolOptions},
+};
+use std::{io::Cursor, sync::Arc};
+
+impl WxDecryptor {
+    /// 在内存中解密单个数据库, 返回的数据和解密后的数据库文件相同
+    ///
+    /// `file` 为相对于 `source_path` 的路径, WAL 中已提交的帧会合并进去, 未加密的数据库只合并 WAL
+    pub fn decrypt_in_memory(&self, file: &Path) -> WxResult<Vec<u8>> {
+        let file_db = self.source_path.join(file);
+        let file_wal = file_db.with_extension("db-wal");
+        let encrypted = std::fs::read(&file_db)?;
+        let mut data = if encrypted.starts_with(b"SQLite format 3\x00") {
+            let mut data = encrypted;
+            if file_wal.exists() && data.len() >= 100 {
+                let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+                    1 => 65536,
+                    n => n as usize,
+                };
+                if !page_size.is_power_of_two() || !(512..=65536).contains(&page_size) {
+                    return Err(WxError::custom(format!("数据库文件头中的页大小无效: {}", page_size)));
+                }
+                let mut record = WxDecryptFile::new(file.to_path_buf());
+                let commits = self.read_wal_commits(file, &file_wal, page_size, &mut record)?;
+                self.apply_plain_wal(&file_wal, &mut data, page_size, &commits)?;
+            }
+            data
+        }
+        else {
+            let profile = self.profile_of(&file_db, &encrypted)?;
+            let (byte_key, mac_key) = profile.derive_keys(&self.key, &encrypted[0..16])?;
+            let mac_key = (self.need_check_hmac || self.salvage).then_some(&mac_key);
+            let threads = match self.threads {
+                0 => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+                n => n,
+            };
+            let mut pages: Vec<(u32, &[u8])> =
+                encrypted.chunks(profile.page_size).enumerate().map(|(i, page)| (i as u32 + 1, page)).collect();
+            if self.salvage {
+                pages.pop_if(|(_, page)| page.len() < profile.page_size);
+            }
+            let (mut data, _) = decrypt_pages(&profile, &pages, &byte_key, mac_key, threads, self.salvage)?;
+            if file_wal.exists() {
+                let mut record = WxDecryptFile::new(file.to_path_buf());
+                let commits = self.read_wal_commits(file, &file_wal, profile.page_size, &mut record)?;
+                let mut tags = vec![String::new(); pages.len()];
+                let mut out = Cursor::new(&mut data);
+                self.apply_wal(&file_wal, &mut out, &profile, &byte_key, mac_key, &commits, &mut tags, &[], &mut record)?;
+                if !commits.pages.is_empty() {
+                    data.resize(tags.len() * profile.page_size, 0);
+                }
+            }
+            data
+        };
+        // sqlite 不能反序列化 WAL 模式的数据库
+        if data.len() > 19 {
+            data[18..20].copy_from_slice(&[1, 1]);
+        }
+        Ok(data)
+    }
+    /// 在内存中解密数据库并打开只读的连接池, 解密后的数据不会写入磁盘
+    pub async fn connect_in_memory(&self, file: &Path) -> WxResult<SqlitePool> {
+        memory_pool(Arc::new(self.decrypt_in_memory(file)?), vec![]).await
+    }
+}
+
+/// 打开内存中的数据库, `attach` 中的数据库以指定的名称附加到连接上
+///
+/// 每个连接都会复制一份数据, 所以连接池只有一个连接
+pub(crate) async fn memory_pool(main: Arc<Vec<u8>>, attach: Vec<(String, Arc<Vec<u8>>)>) -> WxResult<SqlitePool> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .after_connect(move |connection, _| {
+            let main = main.clone();
+            let attach = attach.clone();
+            Box::pin(async move {
+                connection.deserialize(None, SqliteOwnedBuf::try_from(main.as_slice())?, true).await?;
+                for (name, data) in attach {
+                    connection.execute(format!("ATTACH DATABASE ':memory:' AS \"{}\"", name).as_str()).await?;
+                    connection.deserialize(Some(&name), SqliteOwnedBuf::try_from(data.as_slice())?, true).await?;
+                }
+                Ok(())
+            })
+        })
+        .connect("sqlite::memory:")
+        .await?;
+    Ok(pool)
+}
@@ -0,0 +1,101 @@
+use super::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::UNIX_EPOCH};
+
+/// 增量解密的清单, 保存在解密文件夹中
+const MANIFEST_FILE: &str = "wxdump-manifest.json";
+
+/// 上次解密时每个文件的状态, 键为相对于加密数据库文件夹的路径
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct DecryptManifest {
+    pub files: BTreeMap<String, FileManifest>,
+}
+
+/// 单个数据库上次解密时的状态
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(super) struct FileManifest {
+    pub db: FileStat,
+    pub wal: Option<FileStat>,
+    pub merge_wal: bool,
+    pub salt: String,
+    pub page_size: usize,
+    pub reserve: usize,
+    /// 解密结果中每一页对应的密文页的 hmac, 空字符串表示全零的页
+    pub pages: Vec<String>,
+    /// 不合并 WAL 时, 已经解密的 WAL 帧
+    pub wal_frames: Option<WalManifest>,
+}
+
+/// 已经解密的 WAL 帧, 没有变化时从最后一帧之后继续解密
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(super) struct WalManifest {
+    pub header: String,
+    /// 每一帧的帧头和 hmac
+    pub frames: Vec<String>,
+    /// 最后一帧之后加密前和解密后的校验和
+    pub sums: [u32; 4],
+}
+
+/// 文件的大小和修改时间
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct FileStat {
+    pub size: u64,
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+}
+
+impl DecryptManifest {
+    /// 读取解密文件夹中的清单, 不存在或者无法解析时返回空清单
+    pub fn load(output_path: &Path) -> Self {
+        let path = output_path.join(MANIFEST_FILE);
+        let Ok(text) = std::fs::read_to_string(&path)
+        else {
+            return Self::default();
+        };
+        serde_json::from_str(&text).unwrap_or_else(|e| {
+            warn!("清单无法解析, 将重新解密所有文件: {}, {}", path.display(), e);
+            Self::default()
+        })
+    }
+    /// 先写入临时文件再替换, 避免中断时留下不完整的清单
+    pub fn save(&self, output_path: &Path) -> WxResult<()> {
+        let path = output_path.join(MANIFEST_FILE);
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_string(self)?)?;
+        std::fs::rename(&temp, &path)?;
+        Ok(())
+    }
+    pub fn key(file: &Path) -> String {
+        file.to_string_lossy().replace('\\', "/")
+    }
+}
+
+impl FileManifest {
+    /// 数据库和 WAL 的大小和修改时间都没有变化
+    pub fn is_unchanged(&self, db: &FileStat, wal: Option<&FileStat>, merge_wal: bool) -> bool {
+        self.db == *db && self.wal.as_ref() == wal && self.merge_wal == merge_wal
+    }
+}
+
+impl FileStat {
+    pub fn new(path: &Path) -> WxResult<Self> {
+        let metadata = path.metadata()?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Ok(Self { size: metadata.len(), modified_secs: modified.as_secs(), modified_nanos: modified.subsec_nanos() })
+    }
+}
+
+/// 密文页中保存的 hmac, 页的内容变化时一定会变化
+pub(super) fn page_tag(profile: &CipherProfile, page: &[u8]) -> String {
+    let start = profile.page_size - profile.reserve + 16;
+    page.get(start..start + profile.hmac_size()).map(to_hex).unwrap_or_default()
+}
+
+/// WAL 帧的帧头和 hmac, 帧头中有累积的校验和, 所以前面的帧变化时后面的帧也会变化
+pub(super) fn frame_tag(profile: &CipherProfile, frame: &[u8]) -> String {
+    to_hex(&frame[..24]) + &page_tag(profile, &frame[24..])
+}
+
+pub(super) fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|i| format!("{:02x}", i)).collect()
+}
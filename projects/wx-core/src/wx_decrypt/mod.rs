@@ -1,30 +1,115 @@
-use crate::{WxResult, errors::WxError};
+use crate::{WxResult, WxSalvage, errors::WxError};
 use aes::cipher::{BlockDecryptMut, KeyIvInit, block_padding::NoPadding};
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 use hmac::{Hmac, Mac};
 use sha1::Sha1;
 use std::{
     ffi::OsStr,
-    fs::{File, create_dir_all},
-    io::{Read, Write},
-    path::PathBuf,
+    fs::{File, OpenOptions, create_dir_all},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{Sender, channel},
+    },
+    time::Instant,
 };
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 use url::Url;
 use walkdir::WalkDir;
 
+pub use self::{
+    cipher_profile::{CipherProfile, HmacAlgorithm},
+    reader::DecryptedReader,
+    report::{WxDecryptFile, WxDecryptReport, WxDecryptStatus},
+};
+
+pub(crate) use self::in_memory::memory_pool;
+
+use self::{
+    apply_wal::WalCommits,
+    manifest::{DecryptManifest, FileManifest, FileStat, WalManifest, frame_tag, page_tag, to_hex},
+};
+
+mod apply_wal;
+mod cipher_profile;
+mod in_memory;
+mod manifest;
+mod reader;
+mod report;
+mod verify_key;
+
+/// 每批并行解密的页数
+const BATCH_PAGES: usize = 256;
+
 /// 解密微信数据库
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct WxDecryptor {
+    /// 加密数据库所在的文件夹
     pub source_path: PathBuf,
+    /// 解密后的数据库存放的文件夹
     pub output_path: PathBuf,
+    /// 数据库秘钥
     pub key: [u8; 32],
     /// 是否需要校验 hmac
     pub need_check_hmac: bool,
+    /// 数据库加密参数, 为空时根据第一页自动识别
+    pub cipher: Option<CipherProfile>,
+    /// 解密使用的线程数, 为 0 时使用所有核心
+    pub threads: usize,
+    /// 接收解密进度
+    pub progress: Option<Sender<WxDecryptProgress>>,
+    /// 把 WAL 中已提交的帧合并进数据库, 不再输出 `-wal` 和 `-shm` 文件
+    pub merge_wal: bool,
+    /// 增量解密, 在解密文件夹中保存清单, 跳过没有变化的文件, 只重写变化的页和新的 WAL 帧
+    pub incremental: bool,
+    /// 抢救模式, 用于复制时正在写入的数据库
+    ///
+    /// 校验失败的页替换为全零的页, 丢弃末尾不完整的页, 然后从叶子页中抢救 `MSG` 表的记录到 `*.salvage.db`
+    pub salvage: bool,
+    /// 只解密这些文件, 路径相对于 `source_path`, 为空时解密文件夹下所有的数据库
+    pub files: Vec<PathBuf>,
+}
+
+/// 单个文件的解密进度, 数据库和 WAL 的字节数合并计算
+#[derive(Clone, Debug)]
+pub struct WxDecryptProgress {
+    /// 相对于加密数据库文件夹的路径
+    pub file: PathBuf,
+    /// 已经解密的字节数
+    pub bytes_done: u64,
+    /// 需要解密的总字节数
+    pub bytes_total: u64,
+}
+
+/// 用加密数据库的第一页校验秘钥
+#[derive(Clone, Debug)]
+pub struct WxKeyChecker {
+    first: Vec<u8>,
+}
+
+impl WxKeyChecker {
+    /// 读取加密数据库的第一页, 一般使用 `MicroMsg.db`
+    pub fn new(path: &Path) -> WxResult<Self> {
+        let mut page = vec![0u8; 4096];
+        File::open(path)?.read_exact(&mut page)?;
+        if page.starts_with(b"SQLite format 3\x00") {
+            return Err(WxError::custom(format!("数据库未加密, 无法用于校验秘钥: {}", path.display())));
+        }
+        Ok(Self { first: page })
+    }
+    /// 检查秘钥能否解密该数据库, 会尝试所有内置的加密参数
+    pub fn check(&self, key: &[u8]) -> bool {
+        CipherProfile::detect(key, &self.first).is_some()
+    }
 }
 
 impl WxDecryptor {
-    pub async fn decrypt(&self) -> WxResult<()> {
+    /// 解密文件夹下所有的数据库
+    ///
+    /// 单个文件解密失败不会中断其他文件, 每个文件的结果记录在返回的报告中
+    pub async fn decrypt(&self) -> WxResult<WxDecryptReport> {
+        let start = Instant::now();
         if self.output_path.exists() {
             if !self.output_path.is_dir() {
                 return Err(WxError::custom(format!(
@@ -35,222 +120,553 @@ impl WxDecryptor {
         }
         else {
             // create_dir_all(&self.output_path)?;
-            create_dir_all(&self.output_path.join("Multi"))?;
+            create_dir_all(self.output_path.join("Multi"))?;
         }
-        match Url::from_file_path(&self.source_path) {
-            Ok(o) => println!("原始路径: {}", o),
-            Err(_) => {}
+        if let Ok(o) = Url::from_file_path(&self.source_path) {
+            println!("原始路径: {}", o)
         }
-        match Url::from_file_path(&self.output_path) {
-            Ok(o) => println!("解密路径: {}", o),
-            Err(_) => {}
+        if let Ok(o) = Url::from_file_path(&self.output_path) {
+            println!("解密路径: {}", o)
         }
-        for entry in WalkDir::new(&self.source_path) {
-            match entry {
-                Ok(o) => {
-                    if o.path().extension().eq(&Some(OsStr::new("db"))) {
-                        let relative = o.path().strip_prefix(&self.source_path)?;
-                        self.decrypt_file(relative.to_str().unwrap()).await?;
+        let manifest = match self.incremental {
+            true => DecryptManifest::load(&self.output_path),
+            false => DecryptManifest::default(),
+        };
+        let mut files = vec![];
+        let mut report = WxDecryptReport::default();
+        if self.files.is_empty() {
+            for entry in WalkDir::new(&self.source_path) {
+                let o = match entry {
+                    Ok(o) => o,
+                    Err(e) => {
+                        let path = e.path().unwrap_or(&self.source_path);
+                        let mut record = WxDecryptFile::new(path.strip_prefix(&self.source_path).unwrap_or(path).to_path_buf());
+                        record.status = WxDecryptStatus::IoError { message: e.to_string() };
+                        report.files.push(record);
+                        continue;
                     }
+                };
+                if o.path().extension().eq(&Some(OsStr::new("db"))) {
+                    let relative = o.path().strip_prefix(&self.source_path)?;
+                    files.push((relative.to_path_buf(), o.metadata().map(|m| m.len()).unwrap_or(0)));
                 }
-                Err(_) => {}
             }
         }
-        Ok(())
+        else {
+            for file in &self.files {
+                let size = std::fs::metadata(self.source_path.join(file)).map(|m| m.len()).unwrap_or(0);
+                files.push((file.clone(), size));
+            }
+        }
+        // 先解密大文件, 减少最后只剩一个线程在工作的时间
+        files.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+        let threads = match self.threads {
+            0 => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            n => n,
+        };
+        let workers = threads.min(files.len()).max(1);
+        let page_threads = (threads / files.len().max(1)).max(1);
+        let next = AtomicUsize::new(0);
+        // 每个文件解密完成后立即发送结果, 线程崩溃时只丢失正在解密的文件
+        let (sender, results) = channel();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    let sender = sender.clone();
+                    let (files, next, manifest) = (&files, &next, &manifest);
+                    scope.spawn(move || {
+                        while let Some((file, _)) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            let previous = manifest.files.get(&DecryptManifest::key(file));
+                            let _ = sender.send(self.decrypt_one(file, page_threads, previous));
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                if let Err(e) = handle.join() {
+                    warn!("{}", WxError::thread_panic(e.as_ref()));
+                }
+            }
+        });
+        drop(sender);
+        // 只解密部分文件时保留其他文件的清单
+        let mut next_manifest = match self.files.is_empty() {
+            true => DecryptManifest::default(),
+            false => DecryptManifest { files: manifest.files.clone() },
+        };
+        for (record, entry) in results {
+            if let Some(entry) = entry {
+                next_manifest.files.insert(DecryptManifest::key(&record.file), entry);
+            }
+            report.files.push(record);
+        }
+        // 线程异常退出时, 正在解密的文件没有结果, 记为失败
+        for (file, _) in &files {
+            if !report.files.iter().any(|record| &record.file == file) {
+                let mut record = WxDecryptFile::new(file.clone());
+                record.status = WxDecryptStatus::Failed { message: "解密线程异常退出".to_string() };
+                report.files.push(record);
+            }
+        }
+        if self.salvage {
+            for record in report.files.iter_mut().filter(|r| r.status == WxDecryptStatus::Salvaged) {
+                let file_out = self.output_path.join(&record.file);
+                let salvage = WxSalvage {
+                    source_path: file_out.clone(),
+                    output_path: file_out.with_extension("salvage.db"),
+                    ..Default::default()
+                };
+                match salvage.salvage().await {
+                    Ok(Some(n)) => {
+                        record.warnings.push(format!("从 MSG 表中抢救出 {} 条记录: {}", n, salvage.output_path.display()))
+                    }
+                    Ok(None) => {}
+                    Err(e) => record.warnings.push(format!("抢救 MSG 表失败: {}", e)),
+                }
+            }
+        }
+        if self.incremental {
+            next_manifest.save(&self.output_path)?;
+        }
+        report.files.sort_by(|a, b| a.file.cmp(&b.file));
+        report.elapsed = start.elapsed();
+        Ok(report)
     }
-    async fn decrypt_file(&self, source_file: &str) -> WxResult<()> {
+    /// 解密单个文件, 把错误转换为报告中的状态, 解密失败的文件不会写入清单
+    fn decrypt_one(
+        &self,
+        source_file: &Path,
+        page_threads: usize,
+        previous: Option<&FileManifest>,
+    ) -> (WxDecryptFile, Option<FileManifest>) {
+        let start = Instant::now();
+        let mut record = WxDecryptFile::new(source_file.to_path_buf());
+        let entry = match self.decrypt_file(source_file, page_threads, &mut record, previous) {
+            Ok(o) => o,
+            Err(e) => {
+                debug!("解密失败: {}, {}", source_file.display(), e);
+                record.status = WxDecryptStatus::from(&e);
+                None
+            }
+        };
+        record.elapsed = start.elapsed();
+        (record, entry)
+    }
+    /// 解密单个文件, 返回写入清单的状态, 未加密的文件返回空
+    ///
+    /// `previous` 为上次解密时的状态, 加密参数和盐都没有变化时只重写 hmac 变化的页
+    fn decrypt_file(
+        &self,
+        source_file: &Path,
+        page_threads: usize,
+        record: &mut WxDecryptFile,
+        previous: Option<&FileManifest>,
+    ) -> WxResult<Option<FileManifest>> {
         let file_db = self.source_path.join(source_file);
         let file_wal = file_db.with_extension("db-wal");
-        let file_shm = file_db.with_extension("db-shm");
         let file_out = self.output_path.join(source_file);
-        debug!("正在解密: {}", source_file);
-        let mut saved_file = File::open(&file_db)?;
-        let mut buffer = vec![];
-        saved_file.read_to_end(&mut buffer)?;
-        let salt = &buffer[0..16];
-        let mut byte_key = [0; 32];
-        pbkdf2::pbkdf2::<Hmac<Sha1>>(&self.key, salt, 64000, &mut byte_key)?;
-        let first = &buffer[16..4096];
-        let mac_salt = salt.iter().map(|i| i ^ 58).collect::<Vec<_>>();
-        if check_hmac(first, &byte_key, &mac_salt, 1, 32)? {
-            trace!("密码正确: {}", source_file);
-            let pages: Vec<&[u8]> = buffer[..].chunks(4096).collect();
-            let mut out_file = File::create(&file_out)?;
-            for (index, page) in pages.iter().enumerate() {
-                let mut decrypted_page = vec![];
-                if self.need_check_hmac {
-                    decrypt_data_hmac(index as u32 + 1, page, &byte_key, &mut decrypted_page, 48, 48, &mac_salt, 32)?;
+        let db_stat = FileStat::new(&file_db)?;
+        let wal_stat = FileStat::new(&file_wal).ok();
+        if let Some(previous) = previous {
+            if previous.is_unchanged(&db_stat, wal_stat.as_ref(), self.merge_wal) && file_out.exists() {
+                debug!("没有变化, 跳过: {}", source_file.display());
+                record.status = WxDecryptStatus::Unchanged;
+                record.pages = previous.pages.len() as u64;
+                if !self.merge_wal {
+                    self.copy_shm(source_file, &file_db, &file_out)?;
                 }
-                else {
-                    decrypt_data_hmac_unchecked(index as u32 + 1, page, &byte_key, &mut decrypted_page, 48, 48)?;
+                return Ok(Some(previous.clone()));
+            }
+        }
+        debug!("正在解密: {}", source_file.display());
+        let bytes_total = db_stat.size + wal_stat.as_ref().map(|m| m.size).unwrap_or(0);
+        let mut bytes_done = 0;
+        let mut reader = BufReader::new(File::open(&file_db)?);
+        let mut first = vec![0u8; CipherProfile::BUILTIN.iter().map(|p| p.page_size).max().unwrap_or(4096)];
+        let first_len = read_page(&mut reader, &mut first)?;
+        first.truncate(first_len);
+        if first.starts_with(b"SQLite format 3\x00") {
+            record.status = WxDecryptStatus::NotEncrypted;
+            return Ok(None);
+        }
+        let profile = self.profile_of(&file_db, &first)?;
+        trace!("密码正确: {}, {:?}", source_file.display(), profile);
+        let salt = to_hex(&first[0..16]);
+        let previous = previous.filter(|p| {
+            p.salt == salt
+                && p.page_size == profile.page_size
+                && p.reserve == profile.reserve
+                && p.merge_wal == self.merge_wal
+                && file_out.exists()
+        });
+        let (byte_key, mac_key) = profile.derive_keys(&self.key, &first[0..16])?;
+        let mac_key = (self.need_check_hmac || self.salvage).then_some(&mac_key);
+        reader.seek(SeekFrom::Start(0))?;
+        if let Some(parent) = file_out.parent() {
+            create_dir_all(parent)?;
+        }
+        let commits = match self.merge_wal && file_wal.exists() {
+            true => self.read_wal_commits(source_file, &file_wal, profile.page_size, record)?,
+            false => WalCommits::default(),
+        };
+        let file = match previous {
+            Some(_) => OpenOptions::new().write(true).open(&file_out)?,
+            None => File::create(&file_out)?,
+        };
+        let mut writer = BufWriter::new(file);
+        let mut tags = vec![];
+        let mut batch = vec![0u8; profile.page_size * BATCH_PAGES];
+        let mut index = 1;
+        let mut written = 0;
+        loop {
+            let length = read_page(&mut reader, &mut batch)?;
+            if length == 0 {
+                break;
+            }
+            let mut pages: Vec<(u32, &[u8])> =
+                batch[..length].chunks(profile.page_size).enumerate().map(|(i, page)| (index + i as u32, page)).collect();
+            if self.salvage {
+                if let Some((i, page)) = pages.pop_if(|(_, page)| page.len() < profile.page_size) {
+                    warn!("{} 第 {} 页不完整, 已丢弃 {} 字节", source_file.display(), i, page.len());
+                    record.dropped_bytes += page.len() as u64;
                 }
-                out_file.write_all(&decrypted_page)?;
             }
-            out_file.flush()?;
-            trace!("解密成功: {}", source_file);
-
-            if file_wal.exists() {
-                let mut save_wal_file = File::open(&file_wal)?;
-                let mut save_wal_buffer = vec![];
-                save_wal_file.read_to_end(&mut save_wal_buffer)?;
-                if save_wal_buffer.len() != 0 {
-                    let order_byte = save_wal_buffer[3];
-                    let (mut dis_decrypt_sum1, mut dis_decrypt_sum2) =
-                        get_check_sum(0, 0, &save_wal_buffer[..24], &order_byte)?;
-                    let (mut decrypted_sum1, mut decrypted_sum2) = get_check_sum(0, 0, &save_wal_buffer[..24], &order_byte)?;
-                    let mut decrypted_wal_file = File::create(&file_out.with_extension("db-wal"))?;
-                    decrypted_wal_file.write_all(&save_wal_buffer[0..32])?;
-                    let wal_frames: Vec<&[u8]> = save_wal_buffer[32..].chunks(24 + 4096).collect();
-                    for wal_frame in wal_frames {
-                        let mut decrypt_buf = vec![];
-                        let mut cur = std::io::Cursor::new(&wal_frame[0..24]);
-                        let page_index = cur.read_u32::<BigEndian>()?;
-                        cur.set_position(16);
-                        let wal_frame_sum1 = cur.read_u32::<BigEndian>()?;
-                        let wal_frame_sum2 = cur.read_u32::<BigEndian>()?;
-                        (dis_decrypt_sum1, dis_decrypt_sum2) =
-                            get_check_sum(dis_decrypt_sum1, dis_decrypt_sum2, &wal_frame[..8], &order_byte)?;
-                        (dis_decrypt_sum1, dis_decrypt_sum2) =
-                            get_check_sum(dis_decrypt_sum1, dis_decrypt_sum2, &wal_frame[24..], &order_byte)?;
-                        if self.need_check_hmac {
-                            decrypt_data_hmac(
-                                page_index,
-                                &wal_frame[24..],
-                                &byte_key,
-                                &mut decrypt_buf,
-                                48,
-                                48,
-                                &mac_salt,
-                                32,
-                            )?;
-                        }
-                        else {
-                            decrypt_data_hmac_unchecked(page_index, &wal_frame[24..], &byte_key, &mut decrypt_buf, 48, 48)?;
-                        }
-                        (decrypted_sum1, decrypted_sum2) =
-                            get_check_sum(decrypted_sum1, decrypted_sum2, &wal_frame[..8], &order_byte)?;
-                        (decrypted_sum1, decrypted_sum2) =
-                            get_check_sum(decrypted_sum1, decrypted_sum2, &decrypt_buf, &order_byte)?;
-
-                        if wal_frame_sum1 == dis_decrypt_sum1 && wal_frame_sum2 == dis_decrypt_sum2 {
-                            decrypted_wal_file.write_all(&wal_frame[0..16])?;
-                            decrypted_wal_file.write_all(&decrypted_sum1.to_be_bytes())?;
-                            decrypted_wal_file.write_all(&decrypted_sum2.to_be_bytes())?;
-                            decrypted_wal_file.write_all(&decrypt_buf)?;
-                        }
-                        else {
-                            decrypted_wal_file.write_all(&wal_frame[0..24])?;
-                            decrypted_wal_file.write_all(&decrypt_buf)?;
-                        }
+            tags.extend(pages.iter().map(|(_, page)| page_tag(&profile, page)));
+            match previous {
+                Some(previous) => {
+                    // 合并 WAL 时, WAL 中已提交的页之后统一处理
+                    let changed: Vec<(u32, &[u8])> = pages
+                        .iter()
+                        .filter(|(i, _)| !commits.pages.contains_key(i))
+                        .filter(|(i, _)| previous.pages.get(*i as usize - 1) != tags.get(*i as usize - 1))
+                        .copied()
+                        .collect();
+                    let (decrypted, zeroed) =
+                        decrypt_pages(&profile, &changed, &byte_key, mac_key, page_threads, self.salvage)?;
+                    record.zeroed_pages.extend(zeroed);
+                    let mut offset = 0;
+                    for (i, page) in &changed {
+                        writer.seek(SeekFrom::Start((*i as u64 - 1) * profile.page_size as u64))?;
+                        writer.write_all(&decrypted[offset..offset + page.len()])?;
+                        offset += page.len();
                     }
-                    trace!("解密成功: {}-wal", source_file);
+                    record.pages_written += changed.len() as u64;
+                }
+                None => {
+                    let (decrypted, zeroed) = decrypt_pages(&profile, &pages, &byte_key, mac_key, page_threads, self.salvage)?;
+                    writer.write_all(&decrypted)?;
+                    record.zeroed_pages.extend(zeroed);
+                    record.pages_written += pages.len() as u64;
                 }
+            }
+            written += pages.iter().map(|(_, page)| page.len() as u64).sum::<u64>();
+            index += pages.len() as u32;
+            record.pages += pages.len() as u64;
+            bytes_done += length as u64;
+            self.report(source_file, bytes_done, bytes_total);
+        }
+        writer.flush()?;
+        let out = writer.into_inner().map_err(|e| e.into_error())?;
+        // 合并 WAL 时数据库的长度由最后一次提交决定
+        if commits.pages.is_empty() {
+            out.set_len(written)?;
+        }
+        drop(out);
+        trace!("解密成功: {}", source_file.display());
 
-                if file_shm.exists() {
-                    tokio::fs::copy(&file_shm, &file_out.with_extension("db-shm")).await?;
-                    trace!("解密成功: {}-shm", source_file);
+        let mut wal_frames = None;
+        if self.merge_wal {
+            let previous_pages = previous.map(|p| p.pages.as_slice()).unwrap_or_default();
+            let mut out = OpenOptions::new().write(true).open(&file_out)?;
+            self.apply_wal(&file_wal, &mut out, &profile, &byte_key, mac_key, &commits, &mut tags, previous_pages, record)?;
+            if !commits.pages.is_empty() {
+                out.set_len(tags.len() as u64 * profile.page_size as u64)?;
+            }
+            bytes_done += commits.bytes_read;
+            self.report(source_file, bytes_done, bytes_total);
+            // 没有 WAL 之后改回回滚日志模式, 只读打开时不再需要 `-shm`
+            out.seek(SeekFrom::Start(18))?;
+            out.write_all(&[1, 1])?;
+        }
+        else {
+            let file_out_wal = file_out.with_extension("db-wal");
+            if file_wal.exists() {
+                let previous_wal = previous.and_then(|p| p.wal_frames.as_ref());
+                let (bytes_read, manifest) =
+                    self.decrypt_wal(&file_wal, &file_out_wal, &profile, &byte_key, mac_key, record, previous_wal)?;
+                wal_frames = manifest;
+                bytes_done += bytes_read;
+                self.report(source_file, bytes_done, bytes_total);
+                trace!("解密成功: {}-wal", source_file.display());
+            }
+            else if file_out_wal.exists() {
+                std::fs::remove_file(&file_out_wal)?;
+            }
+            self.copy_shm(source_file, &file_db, &file_out)?;
+        }
+        if !record.zeroed_pages.is_empty() || record.dropped_bytes != 0 {
+            record.status = WxDecryptStatus::Salvaged;
+        }
+        Ok(Some(FileManifest {
+            db: db_stat,
+            wal: wal_stat,
+            merge_wal: self.merge_wal,
+            salt,
+            page_size: profile.page_size,
+            reserve: profile.reserve,
+            pages: tags,
+            wal_frames,
+        }))
+    }
+    /// 解密 WAL, 返回读取的字节数和写入清单的状态
+    ///
+    /// 文件头和 `previous` 中所有的帧都没有变化时, 从上次的位置继续追加新的帧
+    #[allow(clippy::too_many_arguments)]
+    fn decrypt_wal(
+        &self,
+        file_wal: &Path,
+        file_out_wal: &Path,
+        profile: &CipherProfile,
+        byte_key: &[u8],
+        mac_key: Option<&[u8; 32]>,
+        record: &mut WxDecryptFile,
+        previous: Option<&WalManifest>,
+    ) -> WxResult<(u64, Option<WalManifest>)> {
+        let mut reader = BufReader::new(File::open(file_wal)?);
+        let mut header = [0u8; 32];
+        let header_len = read_page(&mut reader, &mut header)?;
+        if header_len == 0 {
+            if file_out_wal.exists() {
+                std::fs::remove_file(file_out_wal)?;
+            }
+            return Ok((0, None));
+        }
+        let frame_len = 24 + profile.page_size;
+        let order_byte = header[3];
+        let (mut dis_decrypt_sum1, mut dis_decrypt_sum2) = get_check_sum(0, 0, &header[..24], &order_byte)?;
+        let (mut decrypted_sum1, mut decrypted_sum2) = (dis_decrypt_sum1, dis_decrypt_sum2);
+        let mut wal_frame = vec![0u8; frame_len];
+        let resume = previous.filter(|p| {
+            let expect_len = 32 + p.frames.len() as u64 * frame_len as u64;
+            p.header == to_hex(&header[..header_len])
+                && file_out_wal.metadata().map(|m| m.len()).ok() == Some(expect_len)
+                && p.frames.iter().all(|tag| {
+                    matches!(read_page(&mut reader, &mut wal_frame), Ok(n) if n == frame_len)
+                        && frame_tag(profile, &wal_frame) == *tag
+                })
+        });
+        let mut frames = vec![];
+        let mut decrypted_wal_file = match resume {
+            Some(p) => {
+                [dis_decrypt_sum1, dis_decrypt_sum2, decrypted_sum1, decrypted_sum2] = p.sums;
+                frames = p.frames.clone();
+                let mut out = OpenOptions::new().write(true).open(file_out_wal)?;
+                out.seek(SeekFrom::End(0))?;
+                BufWriter::new(out)
+            }
+            None => {
+                reader.seek(SeekFrom::Start(header_len as u64))?;
+                let mut out = BufWriter::new(File::create(file_out_wal)?);
+                out.write_all(&header[..header_len])?;
+                out
+            }
+        };
+        let mut complete = header_len == 32;
+        let mut decrypt_buf = Vec::with_capacity(profile.page_size);
+        loop {
+            let length = read_page(&mut reader, &mut wal_frame)?;
+            if length == 0 {
+                break;
+            }
+            // 写到一半的帧之后没有有效的数据, 下次需要重新解密整个 WAL
+            if length < frame_len {
+                warn!("{} 末尾有 {} 字节不完整的帧, 已忽略", file_wal.display(), length);
+                record.warnings.push(format!("WAL 末尾有 {} 字节不完整的帧, 已忽略", length));
+                complete = false;
+                break;
+            }
+            let wal_frame = &wal_frame[..length];
+            record.wal_frames += 1;
+            decrypt_buf.clear();
+            let mut cur = std::io::Cursor::new(&wal_frame[0..24]);
+            let page_index = cur.read_u32::<BigEndian>()?;
+            cur.set_position(16);
+            let wal_frame_sum1 = cur.read_u32::<BigEndian>()?;
+            let wal_frame_sum2 = cur.read_u32::<BigEndian>()?;
+            (dis_decrypt_sum1, dis_decrypt_sum2) =
+                get_check_sum(dis_decrypt_sum1, dis_decrypt_sum2, &wal_frame[..8], &order_byte)?;
+            (dis_decrypt_sum1, dis_decrypt_sum2) =
+                get_check_sum(dis_decrypt_sum1, dis_decrypt_sum2, &wal_frame[24..], &order_byte)?;
+            if let Err(e) = decrypt_data(profile, page_index, &wal_frame[24..], byte_key, &mut decrypt_buf, mac_key) {
+                if !self.salvage {
+                    return Err(e);
                 }
-                Ok(())
+                warn!("{} 中第 {} 页的帧解密失败, 已替换为全零的页: {}", file_wal.display(), page_index, e);
+                record.zeroed_pages.push(page_index);
+                decrypt_buf.clear();
+                decrypt_buf.resize(wal_frame.len() - 24, 0);
+            }
+            (decrypted_sum1, decrypted_sum2) = get_check_sum(decrypted_sum1, decrypted_sum2, &wal_frame[..8], &order_byte)?;
+            (decrypted_sum1, decrypted_sum2) = get_check_sum(decrypted_sum1, decrypted_sum2, &decrypt_buf, &order_byte)?;
+
+            if wal_frame_sum1 == dis_decrypt_sum1 && wal_frame_sum2 == dis_decrypt_sum2 {
+                decrypted_wal_file.write_all(&wal_frame[0..16])?;
+                decrypted_wal_file.write_all(&decrypted_sum1.to_be_bytes())?;
+                decrypted_wal_file.write_all(&decrypted_sum2.to_be_bytes())?;
+                decrypted_wal_file.write_all(&decrypt_buf)?;
             }
             else {
-                Ok(())
+                decrypted_wal_file.write_all(&wal_frame[0..24])?;
+                decrypted_wal_file.write_all(&decrypt_buf)?;
             }
+            frames.push(frame_tag(profile, wal_frame));
         }
-        else {
-            Err(WxError::invalid_key(self.key, &self.source_path))?
+        decrypted_wal_file.flush()?;
+        let manifest = complete.then(|| WalManifest {
+            header: to_hex(&header),
+            frames,
+            sums: [dis_decrypt_sum1, dis_decrypt_sum2, decrypted_sum1, decrypted_sum2],
+        });
+        Ok((reader.stream_position()?, manifest))
+    }
+    /// `-shm` 只是索引, 直接复制
+    fn copy_shm(&self, source_file: &Path, file_db: &Path, file_out: &Path) -> WxResult<()> {
+        let file_shm = file_db.with_extension("db-shm");
+        let file_out_shm = file_out.with_extension("db-shm");
+        if file_shm.exists() {
+            std::fs::copy(&file_shm, &file_out_shm)?;
+            trace!("解密成功: {}-shm", source_file.display());
+        }
+        else if file_out_shm.exists() {
+            std::fs::remove_file(&file_out_shm)?;
+        }
+        Ok(())
+    }
+    /// 使用指定的加密参数或者根据第一页识别
+    fn profile_of(&self, file_db: &Path, first: &[u8]) -> WxResult<CipherProfile> {
+        CipherProfile::select(self.cipher, &self.key, first)?.ok_or(WxError::invalid_key(self.key, file_db))
+    }
+    fn report(&self, file: &Path, bytes_done: u64, bytes_total: u64) {
+        if let Some(sender) = &self.progress {
+            let _ = sender.send(WxDecryptProgress { file: file.to_path_buf(), bytes_done, bytes_total });
+        }
+    }
+}
+
+/// 把一批页分给多个线程解密, 按顺序拼接解密后的页
+///
+/// `salvage` 为真时解密失败的页替换为全零的页, 同时返回这些页的页号
+fn decrypt_pages(
+    profile: &CipherProfile,
+    pages: &[(u32, &[u8])],
+    key: &[u8],
+    mac_key: Option<&[u8; 32]>,
+    threads: usize,
+    salvage: bool,
+) -> WxResult<(Vec<u8>, Vec<u32>)> {
+    let decrypt_chunk = |chunk: &[(u32, &[u8])]| -> WxResult<(Vec<u8>, Vec<u32>)> {
+        let mut decrypted = Vec::with_capacity(chunk.len() * profile.page_size);
+        let mut zeroed = vec![];
+        for (index, page) in chunk {
+            let start = decrypted.len();
+            if let Err(e) = decrypt_data(profile, *index, page, key, &mut decrypted, mac_key) {
+                if !salvage {
+                    return Err(e);
+                }
+                warn!("第 {} 页解密失败, 已替换为全零的页: {}", index, e);
+                decrypted.truncate(start);
+                decrypted.resize(start + page.len(), 0);
+                zeroed.push(*index);
+            }
+        }
+        Ok((decrypted, zeroed))
+    };
+    if threads <= 1 || pages.len() <= 1 {
+        return decrypt_chunk(pages);
+    }
+    let size = pages.len().div_ceil(threads);
+    let chunks: Vec<(Vec<u8>, Vec<u32>)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = pages.chunks(size).map(|chunk| scope.spawn(move || decrypt_chunk(chunk))).collect();
+        handles
+            .into_iter()
+            // 崩溃转换为错误, 只影响当前文件
+            .map(|h| h.join().map_err(|e| WxError::thread_panic(e.as_ref()))?)
+            .collect::<WxResult<Vec<_>>>()
+    })?;
+    let mut decrypted = Vec::with_capacity(pages.len() * profile.page_size);
+    let mut zeroed = vec![];
+    for (data, pages) in chunks {
+        decrypted.extend(data);
+        zeroed.extend(pages);
+    }
+    Ok((decrypted, zeroed))
+}
+
+/// 尽量读满一页, 只有到达文件末尾时才会返回较短的长度
+pub(crate) fn read_page<R: Read>(reader: &mut R, buffer: &mut [u8]) -> WxResult<usize> {
+    let mut length = 0;
+    while length < buffer.len() {
+        match reader.read(&mut buffer[length..])? {
+            0 => break,
+            n => length += n,
         }
     }
+    Ok(length)
 }
 
 fn get_check_sum(mut s1: u32, mut s2: u32, list: &[u8], order_byte: &u8) -> WxResult<(u32, u32)> {
     let get_i32 = if *order_byte == 0x82 {
-        |cursor: &mut std::io::Cursor<&[u8]>| cursor.read_u32::<LittleEndian>().unwrap()
+        |cursor: &mut std::io::Cursor<&[u8]>| cursor.read_u32::<LittleEndian>()
     }
     else if *order_byte == 0x83 {
-        |cursor: &mut std::io::Cursor<&[u8]>| cursor.read_u32::<BigEndian>().unwrap()
+        |cursor: &mut std::io::Cursor<&[u8]>| cursor.read_u32::<BigEndian>()
     }
     else {
         return Err(WxError::custom("bad order_byte"));
     };
-    let mut list = list.chunks(4).map(|data| {
-        let mut cursor = std::io::Cursor::new(&data[..]);
-        get_i32(&mut cursor)
-    });
-    loop {
-        if let Some(first) = list.next() {
-            s1 = u32::wrapping_add(u32::wrapping_add(s1, first), s2);
-            s2 = u32::wrapping_add(u32::wrapping_add(s2, list.next().unwrap()), s1);
-        }
-        else {
-            break;
-        }
+    if !list.len().is_multiple_of(8) {
+        return Err(WxError::custom(format!("校验和的数据长度不是 8 的倍数: {}", list.len())));
+    }
+    let mut cursor = std::io::Cursor::new(list);
+    while (cursor.position() as usize) < list.len() {
+        s1 = u32::wrapping_add(u32::wrapping_add(s1, get_i32(&mut cursor)?), s2);
+        s2 = u32::wrapping_add(u32::wrapping_add(s2, get_i32(&mut cursor)?), s1);
     }
     Ok((s1, s2))
 }
 
-fn decrypt_data<F>(
+/// 解密一页数据, 第一页开头的盐会被替换为 SQLite 的文件头, 保留区原样写回
+///
+/// `mac_key` 为空时不校验 hmac
+fn decrypt_data(
+    profile: &CipherProfile,
     index: u32,
     data: &[u8],
     key: &[u8],
     decrypted_data: &mut Vec<u8>,
-    iv_offset: usize,
-    dis_decrypted_offset: usize,
-    check_fn: F,
-) -> WxResult<()>
-where
-    F: Fn(&[u8], &[u8], u32) -> WxResult<bool>,
-{
+    mac_key: Option<&[u8; 32]>,
+) -> WxResult<()> {
     let page = if index == 1 {
         decrypted_data.append(&mut "SQLite format 3\x00".as_bytes().to_vec());
-        &data[16..]
+        data.get(16..).ok_or(WxError::custom(format!("数据长度不足, index: {}", index)))?
     }
     else {
-        &data[..]
+        data
     };
-    if !check_fn(page, key, index)? {
-        return Err(WxError::custom(format!("数据校验未通过, index: {}", index)));
+    profile.validate()?;
+    if page.len() < profile.reserve {
+        return Err(WxError::custom(format!("数据长度不足, index: {}", index)));
     }
-    let iv = &page[page.len() - iv_offset..page.len() - iv_offset + 16];
-    let mut decrypt_buf = vec![0u8; page.len() - dis_decrypted_offset];
-    let decryptor = cbc::Decryptor::<aes::Aes256>::new_from_slices(&key, iv)?;
-    decryptor.decrypt_padded_b2b_mut::<NoPadding>(&page[..page.len() - dis_decrypted_offset], &mut decrypt_buf)?;
+    if let Some(mac_key) = mac_key {
+        if !profile.check_page(page, mac_key, index)? {
+            return Err(WxError::invalid_hmac(index));
+        }
+    }
+    let iv = &page[page.len() - profile.reserve..page.len() - profile.reserve + 16];
+    let mut decrypt_buf = vec![0u8; page.len() - profile.reserve];
+    let decryptor = cbc::Decryptor::<aes::Aes256>::new_from_slices(key, iv)?;
+    decryptor.decrypt_padded_b2b_mut::<NoPadding>(&page[..page.len() - profile.reserve], &mut decrypt_buf)?;
     decrypted_data.append(&mut decrypt_buf);
-    decrypted_data.append(&mut data[data.len() - dis_decrypted_offset..].to_vec());
+    decrypted_data.append(&mut data[data.len() - profile.reserve..].to_vec());
     Ok(())
 }
-
-fn decrypt_data_hmac(
-    index: u32,
-    data: &[u8],
-    key: &[u8],
-    decrypted_data: &mut Vec<u8>,
-    iv_offset: usize,
-    dis_decrypted_offset: usize,
-    salt: &[u8],
-    hmac_offset: usize,
-) -> WxResult<()> {
-    decrypt_data(index, data, key, decrypted_data, iv_offset, dis_decrypted_offset, |data, key, index| {
-        check_hmac(data, key, salt, index, hmac_offset)
-    })
-}
-
-fn check_hmac(data: &[u8], key: &[u8], salt: &[u8], index: u32, offset_hmac: usize) -> WxResult<bool> {
-    let mut mac_key = [0u8; 32];
-    pbkdf2::pbkdf2::<Hmac<Sha1>>(&key, &salt, 2, &mut mac_key)?;
-    let mut hash_mac = Hmac::<Sha1>::new_from_slice(&mac_key)?;
-    hash_mac.update(&data[..data.len() - offset_hmac]);
-    hash_mac.update(&index.to_le_bytes());
-    let r = hash_mac.finalize().into_bytes();
-    Ok(r[..] == data[data.len() - offset_hmac..data.len() - offset_hmac + 20])
-}
-
-fn decrypt_data_hmac_unchecked(
-    index: u32,
-    data: &[u8],
-    key: &[u8],
-    decrypted_data: &mut Vec<u8>,
-    offset_iv: usize,
-    dis_decrypted_offset: usize,
-) -> WxResult<()> {
-    decrypt_data(index, data, key, decrypted_data, offset_iv, dis_decrypted_offset, |_, _, _| Ok(true))
-}
@@ -0,0 +1,153 @@
+use super::*;
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Formatter},
+    io::ErrorKind,
+};
+
+/// 默认缓存的页数
+const CACHE_PAGES: usize = 16;
+
+/// 按需解密的数据库读取器, 读到的内容和解密后的数据库文件相同
+///
+/// 只在读取时解密用到的页, 最近使用的页保存在 LRU 缓存中
+pub struct DecryptedReader<R> {
+    inner: R,
+    profile: CipherProfile,
+    byte_key: [u8; 32],
+    mac_key: [u8; 32],
+    check_hmac: bool,
+    length: u64,
+    position: u64,
+    cache: VecDeque<(u32, Vec<u8>)>,
+    cache_pages: usize,
+}
+
+impl<R> Debug for DecryptedReader<R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecryptedReader")
+            .field("profile", &self.profile)
+            .field("check_hmac", &self.check_hmac)
+            .field("length", &self.length)
+            .field("position", &self.position)
+            .field("cached", &self.cache.iter().map(|(index, _)| *index).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<R: Read + Seek> DecryptedReader<R> {
+    /// 读取第一页识别加密参数, 默认校验每一页的 hmac
+    pub fn new(inner: R, key: [u8; 32]) -> WxResult<Self> {
+        Self::open(inner, key, None)
+    }
+    /// 使用指定的加密参数
+    pub fn with_profile(inner: R, key: [u8; 32], profile: CipherProfile) -> WxResult<Self> {
+        Self::open(inner, key, Some(profile))
+    }
+    fn open(mut inner: R, key: [u8; 32], cipher: Option<CipherProfile>) -> WxResult<Self> {
+        let length = inner.seek(SeekFrom::End(0))?;
+        inner.seek(SeekFrom::Start(0))?;
+        let mut first = vec![0u8; CipherProfile::BUILTIN.iter().map(|p| p.page_size).max().unwrap_or(4096)];
+        let first_len = read_page(&mut inner, &mut first)?;
+        first.truncate(first_len);
+        let profile = CipherProfile::select(cipher, &key, &first)?.ok_or(WxError::custom("秘钥不匹配, 无法解密数据库"))?;
+        let (byte_key, mac_key) = profile.derive_keys(&key, &first[0..16])?;
+        Ok(Self {
+            inner,
+            profile,
+            byte_key,
+            mac_key,
+            check_hmac: true,
+            length,
+            position: 0,
+            cache: VecDeque::new(),
+            cache_pages: CACHE_PAGES,
+        })
+    }
+    /// 是否校验 hmac, 关闭后可以读取部分损坏的数据库
+    pub fn set_check_hmac(&mut self, check: bool) {
+        // 缓存中的页可能没有校验过
+        self.cache.clear();
+        self.check_hmac = check;
+    }
+    /// 设置缓存的页数, 至少为 1
+    pub fn set_cache_pages(&mut self, pages: usize) {
+        self.cache_pages = pages.max(1);
+        self.cache.truncate(self.cache_pages);
+    }
+    /// 识别到的加密参数
+    pub fn profile(&self) -> CipherProfile {
+        self.profile
+    }
+    /// 取回内部的读取器
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+    /// 读取并解密一页, 页号从 1 开始
+    fn page(&mut self, index: u32) -> WxResult<&[u8]> {
+        match self.cache.iter().position(|(i, _)| *i == index) {
+            Some(position) => {
+                let entry = self.cache.remove(position).unwrap_or_default();
+                self.cache.push_front(entry);
+            }
+            None => {
+                let page_size = self.profile.page_size as u64;
+                let start = (index as u64 - 1) * page_size;
+                let mut encrypted = vec![0u8; (self.length - start).min(page_size) as usize];
+                self.inner.seek(SeekFrom::Start(start))?;
+                self.inner.read_exact(&mut encrypted)?;
+                let mut decrypted = Vec::with_capacity(encrypted.len());
+                decrypt_data(
+                    &self.profile,
+                    index,
+                    &encrypted,
+                    &self.byte_key,
+                    &mut decrypted,
+                    self.check_hmac.then_some(&self.mac_key),
+                )?;
+                self.cache.truncate(self.cache_pages - 1);
+                self.cache.push_front((index, decrypted));
+            }
+        }
+        Ok(&self.cache[0].1)
+    }
+}
+
+impl<R: Read + Seek> Read for DecryptedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let page_size = self.profile.page_size as u64;
+        let mut done = 0;
+        while done < buf.len() && self.position < self.length {
+            let index = (self.position / page_size) as u32 + 1;
+            let offset = (self.position % page_size) as usize;
+            let page = match self.page(index) {
+                Ok(o) => o,
+                // 先返回已经读到的数据, 下次读取时再报告错误
+                Err(_) if done > 0 => break,
+                Err(e) => return Err(std::io::Error::new(ErrorKind::InvalidData, e.to_string())),
+            };
+            let length = (page.len() - offset).min(buf.len() - done);
+            buf[done..done + length].copy_from_slice(&page[offset..offset + length]);
+            done += length;
+            self.position += length as u64;
+        }
+        Ok(done)
+    }
+}
+
+impl<R: Read + Seek> Seek for DecryptedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.length.checked_add_signed(n),
+            SeekFrom::Current(n) => self.position.checked_add_signed(n),
+        };
+        match position {
+            Some(n) => {
+                self.position = n;
+                Ok(n)
+            }
+            None => Err(std::io::Error::new(ErrorKind::InvalidInput, "不能移动到文件开头之前")),
+        }
+    }
+}
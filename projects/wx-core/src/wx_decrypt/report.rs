@@ -0,0 +1,128 @@
+use super::*;
+use crate::WxErrorKind;
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+
+/// 单个文件的解密状态
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WxDecryptStatus {
+    /// 解密成功
+    Decrypted,
+    /// 文件没有加密, 已跳过
+    NotEncrypted,
+    /// 增量解密时文件没有变化, 已跳过
+    Unchanged,
+    /// 抢救模式下部分页已损坏, 被替换为全零的页或者丢弃
+    Salvaged,
+    /// 秘钥无法解密该文件
+    WrongKey,
+    /// hmac 校验失败
+    HmacFailure {
+        /// 校验失败的页号, 从 1 开始
+        page: u32,
+    },
+    /// 读写文件失败或数据损坏
+    IoError {
+        /// 错误信息
+        message: String,
+    },
+    /// 解密时线程意外崩溃, 其他文件不受影响
+    Failed {
+        /// 崩溃时的信息
+        message: String,
+    },
+}
+
+/// 单个文件的解密报告
+#[derive(Clone, Debug)]
+pub struct WxDecryptFile {
+    /// 相对于加密数据库文件夹的路径
+    pub file: PathBuf,
+    /// 解密状态
+    pub status: WxDecryptStatus,
+    /// 数据库的页数
+    pub pages: u64,
+    /// 实际解密并写入的页数, 增量解密时只包含变化的页
+    pub pages_written: u64,
+    /// 处理的 WAL 帧数
+    pub wal_frames: u64,
+    /// 抢救模式下被替换为全零的页
+    pub zeroed_pages: Vec<u32>,
+    /// 抢救模式下丢弃的末尾不完整的页的字节数
+    pub dropped_bytes: u64,
+    /// 解密耗时
+    pub elapsed: Duration,
+    /// 解密过程中被忽略的问题, 例如 WAL 中盐或校验和不匹配的帧
+    pub warnings: Vec<String>,
+}
+
+/// 一次解密的完整报告
+#[derive(Clone, Debug, Default)]
+pub struct WxDecryptReport {
+    /// 找到的所有文件, 按路径排序
+    pub files: Vec<WxDecryptFile>,
+    /// 总耗时
+    pub elapsed: Duration,
+}
+
+impl Display for WxDecryptStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Decrypted => write!(f, "解密成功"),
+            Self::NotEncrypted => write!(f, "未加密, 已跳过"),
+            Self::Unchanged => write!(f, "未修改, 已跳过"),
+            Self::Salvaged => write!(f, "部分页已损坏, 已抢救"),
+            Self::WrongKey => write!(f, "秘钥错误"),
+            Self::HmacFailure { page } => write!(f, "第 {} 页 hmac 校验失败", page),
+            Self::IoError { message } => write!(f, "读写失败: {}", message),
+            Self::Failed { message } => write!(f, "解密失败: {}", message),
+        }
+    }
+}
+
+impl WxDecryptStatus {
+    /// 解密成功或者无需解密
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Decrypted | Self::NotEncrypted | Self::Unchanged)
+    }
+}
+
+impl From<&WxError> for WxDecryptStatus {
+    fn from(error: &WxError) -> Self {
+        match error.kind() {
+            WxErrorKind::InvalidKey { .. } => Self::WrongKey,
+            WxErrorKind::InvalidHmac { page } => Self::HmacFailure { page: *page },
+            WxErrorKind::ThreadPanic { message } => Self::Failed { message: message.clone() },
+            _ => Self::IoError { message: error.to_string() },
+        }
+    }
+}
+
+impl WxDecryptFile {
+    pub(super) fn new(file: PathBuf) -> Self {
+        Self {
+            file,
+            status: WxDecryptStatus::Decrypted,
+            pages: 0,
+            pages_written: 0,
+            wal_frames: 0,
+            zeroed_pages: vec![],
+            dropped_bytes: 0,
+            elapsed: Duration::ZERO,
+            warnings: vec![],
+        }
+    }
+}
+
+impl WxDecryptReport {
+    /// 是否所有文件都解密成功或者无需解密
+    pub fn is_success(&self) -> bool {
+        self.files.iter().all(|file| file.status.is_success())
+    }
+    /// 指定状态的文件数量
+    pub fn count(&self, predicate: impl Fn(&WxDecryptStatus) -> bool) -> usize {
+        self.files.iter().filter(|file| predicate(&file.status)).count()
+    }
+}
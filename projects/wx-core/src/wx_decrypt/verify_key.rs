@@ -0,0 +1,38 @@
+use super::*;
+
+impl WxDecryptor {
+    /// 校验秘钥能否打开数据库, 只派生秘钥并校验第一页的 hmac, 不会解密数据
+    ///
+    /// 返回能通过校验的加密参数, 数据库未加密时返回错误
+    pub fn verify_key(path: &Path, key: &[u8; 32]) -> WxResult<Option<CipherProfile>> {
+        let mut reader = File::open(path)?;
+        let mut first = vec![0u8; CipherProfile::BUILTIN.iter().map(|p| p.page_size).max().unwrap_or(4096)];
+        let first_len = read_page(&mut reader, &mut first)?;
+        first.truncate(first_len);
+        if first.starts_with(b"SQLite format 3\x00") {
+            return Err(WxError::custom(format!("数据库未加密: {}", path.display())));
+        }
+        Ok(CipherProfile::detect(key, &first))
+    }
+    /// 用同一个秘钥校验文件夹下所有的数据库, 路径相对于 `root`, 按文件名排序
+    ///
+    /// 同一个账号的数据库可能使用不同的秘钥加密, 所以每个数据库都会单独校验
+    pub fn verify_key_tree(root: &Path, key: &[u8; 32]) -> Vec<(PathBuf, WxResult<Option<CipherProfile>>)> {
+        let mut results = vec![];
+        for entry in WalkDir::new(root).sort_by_file_name() {
+            match entry {
+                Ok(o) if o.path().extension().eq(&Some(OsStr::new("db"))) => {
+                    let relative = o.path().strip_prefix(root).unwrap_or(o.path()).to_path_buf();
+                    results.push((relative, Self::verify_key(o.path(), key)));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    let path = e.path().unwrap_or(root);
+                    let relative = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+                    results.push((relative, Err(WxError::custom(e.to_string()))));
+                }
+            }
+        }
+        results
+    }
+}
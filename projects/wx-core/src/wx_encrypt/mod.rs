@@ -0,0 +1,118 @@
+use crate::{CipherProfile, WxResult, errors::WxError, wx_decrypt::read_page};
+use aes::cipher::{BlockEncryptMut, KeyIvInit, block_padding::NoPadding};
+use rand::RngCore;
+use std::{
+    fs::{File, create_dir_all},
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+use tracing::trace;
+
+/// 加密微信数据库, 生成微信可以读取的 SQLCipher 数据库
+///
+/// 明文数据库每页末尾的保留区必须足够存放 IV 和 hmac, 由 [`WxDecryptor`](crate::WxDecryptor) 解密出的数据库总是满足这个条件
+#[derive(Debug, Default)]
+pub struct WxEncryptor {
+    /// 明文数据库文件
+    pub source_path: PathBuf,
+    /// 加密后的数据库文件
+    pub output_path: PathBuf,
+    /// 数据库秘钥
+    pub key: [u8; 32],
+    /// 数据库加密参数, 为空时使用微信 3.x 的参数
+    pub cipher: Option<CipherProfile>,
+    /// 第一页的盐, 为空时随机生成, 盐相同的数据库可以互相替换页
+    pub salt: Option<[u8; 16]>,
+}
+
+impl WxEncryptor {
+    /// 加密数据库, 每页使用随机的 IV
+    pub async fn encrypt(&self) -> WxResult<()> {
+        let profile = self.cipher.unwrap_or(CipherProfile::WECHAT_V3);
+        profile.validate()?;
+        let mut reader = BufReader::new(File::open(&self.source_path)?);
+        let mut page = vec![0u8; profile.page_size];
+        let length = read_page(&mut reader, &mut page)?;
+        check_header(&profile, &page[..length])?;
+        let mut rng = rand::thread_rng();
+        let salt = self.salt.unwrap_or_else(|| {
+            let mut salt = [0u8; 16];
+            rng.fill_bytes(&mut salt);
+            salt
+        });
+        let (byte_key, mac_key) = profile.derive_keys(&self.key, &salt)?;
+        if let Some(parent) = self.output_path.parent() {
+            create_dir_all(parent)?;
+        }
+        let mut writer = BufWriter::new(File::create(&self.output_path)?);
+        let mut encrypted_page = Vec::with_capacity(profile.page_size);
+        let mut index = 1;
+        let mut length = length;
+        while length != 0 {
+            if length != profile.page_size {
+                return Err(WxError::custom(format!("数据库的最后一页不完整, index: {}", index)));
+            }
+            let mut iv = [0u8; 16];
+            rng.fill_bytes(&mut iv);
+            encrypted_page.clear();
+            encrypt_data(&profile, index, &page, &byte_key, &mac_key, &salt, &iv, &mut encrypted_page)?;
+            writer.write_all(&encrypted_page)?;
+            index += 1;
+            length = read_page(&mut reader, &mut page)?;
+        }
+        writer.flush()?;
+        trace!("加密成功: {}", self.output_path.display());
+        Ok(())
+    }
+}
+
+/// 检查明文数据库的页大小和保留区
+fn check_header(profile: &CipherProfile, page: &[u8]) -> WxResult<()> {
+    if page.len() < 100 || !page.starts_with(b"SQLite format 3\x00") {
+        return Err(WxError::custom("不是未加密的 SQLite 数据库"));
+    }
+    let page_size = match u16::from_be_bytes([page[16], page[17]]) {
+        1 => 65536,
+        n => n as usize,
+    };
+    if page_size != profile.page_size {
+        return Err(WxError::custom(format!("数据库页大小为 {}, 加密参数要求 {}", page_size, profile.page_size)));
+    }
+    if (page[20] as usize) < profile.reserve {
+        return Err(WxError::custom(format!("数据库保留区为 {} 字节, 加密参数要求 {} 字节", page[20], profile.reserve)));
+    }
+    Ok(())
+}
+
+/// 加密一页数据, 第一页开头的 SQLite 文件头会被替换为盐
+///
+/// 保留区依次写入 IV 和 hmac, 剩余部分填 0
+#[allow(clippy::too_many_arguments)]
+fn encrypt_data(
+    profile: &CipherProfile,
+    index: u32,
+    data: &[u8],
+    key: &[u8],
+    mac_key: &[u8],
+    salt: &[u8],
+    iv: &[u8],
+    encrypted_data: &mut Vec<u8>,
+) -> WxResult<()> {
+    let page = if index == 1 {
+        encrypted_data.extend_from_slice(salt);
+        &data[16..]
+    }
+    else {
+        data
+    };
+    let start = encrypted_data.len();
+    let mut encrypt_buf = vec![0u8; page.len() - profile.reserve];
+    let encryptor = cbc::Encryptor::<aes::Aes256>::new_from_slices(key, iv)?;
+    encryptor.encrypt_padded_b2b_mut::<NoPadding>(&page[..page.len() - profile.reserve], &mut encrypt_buf)?;
+    encrypted_data.append(&mut encrypt_buf);
+    encrypted_data.extend_from_slice(iv);
+    let hmac = profile.page_hmac(mac_key, &encrypted_data[start..], index)?;
+    encrypted_data.extend_from_slice(&hmac);
+    encrypted_data.resize(start + page.len(), 0);
+    Ok(())
+}
@@ -1,8 +1,12 @@
 use std::path::PathBuf;
 
 /// 导出微信数据库中的数据
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct WxExport {
     /// 数据库所在文件路径
     pub db: PathBuf,
+    /// 数据库秘钥, 指定时 `db` 为加密数据库所在的文件夹, 查询时在内存中解密, 不会写入磁盘
+    pub key: Option<[u8; 32]>,
+    /// 导出文件存放的文件夹, 为空时使用 `db`
+    pub output_path: Option<PathBuf>,
 }
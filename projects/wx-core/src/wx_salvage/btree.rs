@@ -0,0 +1,263 @@
+use crate::{WxResult, errors::WxError};
+use std::collections::BTreeSet;
+
+/// 记录中的一个值
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum SqlValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+/// `sqlite_master` 中的一行
+#[derive(Clone, Debug)]
+pub(super) struct SchemaRow {
+    pub kind: String,
+    pub name: String,
+    pub root: u32,
+    pub sql: String,
+}
+
+/// 明文数据库的 B-tree 扫描器, 越界的页和记录都会被跳过
+#[derive(Debug)]
+pub(super) struct BTreeScanner<'a> {
+    data: &'a [u8],
+    page_size: usize,
+    usable: usize,
+}
+
+impl<'a> BTreeScanner<'a> {
+    pub fn new(data: &'a [u8]) -> WxResult<Self> {
+        if data.len() < 100 || !data.starts_with(b"SQLite format 3\x00") {
+            return Err(WxError::custom("数据库文件头已损坏, 无法读取表结构"));
+        }
+        let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+            1 => 65536,
+            n => n as usize,
+        };
+        if !page_size.is_power_of_two() || !(512..=65536).contains(&page_size) {
+            return Err(WxError::custom(format!("数据库页大小无效: {}", page_size)));
+        }
+        Ok(Self { data, page_size, usable: page_size - data[20] as usize })
+    }
+    /// 读取 `sqlite_master`
+    pub fn schema(&self) -> Vec<SchemaRow> {
+        let mut visited = BTreeSet::new();
+        let mut rows = vec![];
+        for page in self.walk(1, &mut visited) {
+            for (_, values) in self.leaf_records(page, 5, true) {
+                if let [SqlValue::Text(kind), SqlValue::Text(name), _, SqlValue::Integer(root), sql] = values.as_slice() {
+                    let sql = match sql {
+                        SqlValue::Text(s) => s.clone(),
+                        _ => String::new(),
+                    };
+                    rows.push(SchemaRow { kind: kind.clone(), name: name.clone(), root: *root as u32, sql });
+                }
+            }
+        }
+        rows
+    }
+    /// 抢救表中的记录, 先读取 B-tree 中能找到的叶子页, 再扫描不属于任何 B-tree 的叶子页
+    ///
+    /// B-tree 中的记录最多有 `columns` 列, 孤立的叶子页中只保留正好 `columns` 列的记录
+    pub fn table_records(&self, table: &SchemaRow, columns: usize) -> Vec<(i64, Vec<SqlValue>)> {
+        let mut others = BTreeSet::new();
+        self.walk(1, &mut others);
+        for row in self.schema() {
+            if row.root != 0 && row.root != table.root {
+                self.walk(row.root, &mut others);
+            }
+        }
+        let mut owned = others.clone();
+        let mut records = vec![];
+        for page in self.walk(table.root, &mut owned) {
+            records.extend(self.leaf_records(page, columns, false));
+        }
+        for page in 1..=self.page_count() {
+            if !owned.contains(&page) && self.page_type(page) == Some(0x0D) {
+                records.extend(self.leaf_records(page, columns, true));
+            }
+        }
+        records
+    }
+    fn page_count(&self) -> u32 {
+        (self.data.len() / self.page_size) as u32
+    }
+    /// 页号从 1 开始, 只返回可用的部分
+    fn page(&self, index: u32) -> Option<&'a [u8]> {
+        let start = (index as usize).checked_sub(1)? * self.page_size;
+        self.data.get(start..start + self.usable)
+    }
+    /// 第一页的前 100 个字节是文件头
+    fn header_offset(index: u32) -> usize {
+        if index == 1 { 100 } else { 0 }
+    }
+    fn page_type(&self, index: u32) -> Option<u8> {
+        self.page(index)?.get(Self::header_offset(index)).copied()
+    }
+    /// 页中所有单元格的偏移, 以及内部页最右侧的子页
+    fn cells(&self, index: u32) -> Option<(u8, Vec<usize>, Option<u32>)> {
+        let page = self.page(index)?;
+        let offset = Self::header_offset(index);
+        let kind = *page.get(offset)?;
+        let (header, right) = match kind {
+            0x02 | 0x05 => (12, Some(read_u32(page, offset + 8)?)),
+            0x0A | 0x0D => (8, None),
+            _ => return None,
+        };
+        let count = u16::from_be_bytes(page.get(offset + 3..offset + 5)?.try_into().ok()?) as usize;
+        let mut cells = Vec::with_capacity(count);
+        for i in 0..count {
+            let pointer = offset + header + i * 2;
+            let cell = u16::from_be_bytes(page.get(pointer..pointer + 2)?.try_into().ok()?) as usize;
+            if cell < offset + header + count * 2 || cell >= self.usable {
+                return None;
+            }
+            cells.push(cell);
+        }
+        Some((kind, cells, right))
+    }
+    /// 遍历一棵 B-tree, 把经过的页加入 `visited`, 按顺序返回叶子页
+    fn walk(&self, root: u32, visited: &mut BTreeSet<u32>) -> Vec<u32> {
+        let mut leaves = vec![];
+        let mut stack = vec![root];
+        while let Some(index) = stack.pop() {
+            if !visited.insert(index) {
+                continue;
+            }
+            let Some((kind, cells, right)) = self.cells(index)
+            else {
+                continue;
+            };
+            match kind {
+                0x02 | 0x05 => {
+                    let Some(page) = self.page(index)
+                    else {
+                        continue;
+                    };
+                    // 倒序入栈, 保证叶子页按键的顺序出现
+                    stack.extend(right);
+                    stack.extend(cells.iter().rev().filter_map(|cell| read_u32(page, *cell)));
+                }
+                _ => leaves.push(index),
+            }
+        }
+        leaves
+    }
+    /// 读取表的叶子页中的完整记录, `exact` 为真时列数必须等于 `columns`
+    fn leaf_records(&self, index: u32, columns: usize, exact: bool) -> Vec<(i64, Vec<SqlValue>)> {
+        let mut records = vec![];
+        let (Some(page), Some((0x0D, cells, _))) = (self.page(index), self.cells(index))
+        else {
+            return records;
+        };
+        for cell in cells {
+            let Some((rowid, payload)) = self.table_cell(page, cell)
+            else {
+                continue;
+            };
+            match parse_record(&payload) {
+                Some(values) if values.len() == columns || (!exact && values.len() < columns) => records.push((rowid, values)),
+                _ => {}
+            }
+        }
+        records
+    }
+    /// 读取叶子页中的一个单元格, 包括溢出页中的内容
+    fn table_cell(&self, page: &[u8], cell: usize) -> Option<(i64, Vec<u8>)> {
+        let (length, n) = read_varint(page.get(cell..)?)?;
+        let (rowid, m) = read_varint(page.get(cell + n..)?)?;
+        let start = cell + n + m;
+        let length = length as usize;
+        let max_local = self.usable - 35;
+        if length <= max_local {
+            return Some((rowid as i64, page.get(start..start + length)?.to_vec()));
+        }
+        let min_local = (self.usable - 12) * 32 / 255 - 23;
+        let local = match min_local + (length - min_local) % (self.usable - 4) {
+            k if k <= max_local => k,
+            _ => min_local,
+        };
+        let mut payload = page.get(start..start + local)?.to_vec();
+        let mut next = read_u32(page, start + local)?;
+        let mut visited = BTreeSet::new();
+        while payload.len() < length {
+            if next == 0 || !visited.insert(next) {
+                return None;
+            }
+            let overflow = self.page(next)?;
+            let take = (length - payload.len()).min(self.usable - 4);
+            payload.extend_from_slice(overflow.get(4..4 + take)?);
+            next = read_u32(overflow, 0)?;
+        }
+        Some((rowid as i64, payload))
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// SQLite 的变长整数, 返回值和占用的字节数
+fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for i in 0..9 {
+        let byte = *data.get(i)?;
+        if i == 8 {
+            return Some(((value << 8) | byte as u64, 9));
+        }
+        value = (value << 7) | (byte & 0x7F) as u64;
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// 解析一条记录, 长度和编码对不上时认为记录已损坏
+fn parse_record(payload: &[u8]) -> Option<Vec<SqlValue>> {
+    let (header_size, mut offset) = read_varint(payload)?;
+    let header_size = header_size as usize;
+    if header_size > payload.len() {
+        return None;
+    }
+    let mut types = vec![];
+    while offset < header_size {
+        let (serial, n) = read_varint(&payload[offset..header_size])?;
+        types.push(serial);
+        offset += n;
+    }
+    let mut body = header_size;
+    let mut values = Vec::with_capacity(types.len());
+    for serial in types {
+        let size = match serial {
+            0 | 8 | 9 => 0,
+            1 => 1,
+            2 => 2,
+            3 => 3,
+            4 => 4,
+            5 => 6,
+            6 | 7 => 8,
+            10 | 11 => return None,
+            n => ((n - 12) / 2) as usize,
+        };
+        let data = payload.get(body..body + size)?;
+        body += size;
+        values.push(match serial {
+            0 => SqlValue::Null,
+            8 => SqlValue::Integer(0),
+            9 => SqlValue::Integer(1),
+            1..=6 => {
+                // 符号扩展到 64 位
+                let value = data.iter().fold(if data[0] & 0x80 != 0 { -1i64 } else { 0 }, |v, b| (v << 8) | *b as i64);
+                SqlValue::Integer(value)
+            }
+            7 => SqlValue::Real(f64::from_be_bytes(data.try_into().ok()?)),
+            n if n % 2 == 0 => SqlValue::Blob(data.to_vec()),
+            _ => SqlValue::Text(String::from_utf8(data.to_vec()).ok()?),
+        });
+    }
+    (body == payload.len()).then_some(values)
+}
@@ -0,0 +1,108 @@
+use self::btree::{BTreeScanner, SqlValue};
+use crate::WxResult;
+use sqlx::{
+    Connection, Row, SqliteConnection,
+    sqlite::{SqliteArguments, SqliteConnectOptions},
+};
+use std::path::PathBuf;
+
+mod btree;
+
+/// 从损坏的明文数据库中抢救一张表的记录, 写入新的数据库
+///
+/// 直接扫描 B-tree 的叶子页, 不依赖 sqlite 能否打开数据库, 只保留长度和编码都完整的记录
+#[derive(Debug)]
+pub struct WxSalvage {
+    /// 损坏的明文数据库
+    pub source_path: PathBuf,
+    /// 新的数据库, 已经存在时会被覆盖
+    pub output_path: PathBuf,
+    /// 需要抢救的表, 默认为 `MSG`
+    pub table: String,
+}
+
+impl Default for WxSalvage {
+    fn default() -> Self {
+        Self { source_path: PathBuf::new(), output_path: PathBuf::new(), table: "MSG".to_string() }
+    }
+}
+
+impl WxSalvage {
+    /// 抢救记录, 返回写入的记录数, 数据库中没有这张表时返回空
+    pub async fn salvage(&self) -> WxResult<Option<u64>> {
+        let data = tokio::fs::read(&self.source_path).await?;
+        let scanner = BTreeScanner::new(&data)?;
+        let Some(table) =
+            scanner.schema().into_iter().find(|row| row.kind == "table" && row.name.eq_ignore_ascii_case(&self.table))
+        else {
+            return Ok(None);
+        };
+        if self.output_path.exists() {
+            tokio::fs::remove_file(&self.output_path).await?;
+        }
+        let options = SqliteConnectOptions::new().filename(&self.output_path).create_if_missing(true);
+        let mut connection = SqliteConnection::connect_with(&options).await?;
+        sqlx::query(&table.sql).execute(&mut connection).await?;
+        let mut columns = vec![];
+        let mut primary = vec![];
+        for row in
+            sqlx::query("SELECT name, type, pk FROM pragma_table_info(?1)").bind(&table.name).fetch_all(&mut connection).await?
+        {
+            let name: String = row.try_get("name")?;
+            let kind: String = row.try_get("type")?;
+            if row.try_get::<i64, _>("pk")? > 0 {
+                primary.push((columns.len(), kind.eq_ignore_ascii_case("INTEGER")));
+            }
+            columns.push(name);
+        }
+        // 唯一的 INTEGER PRIMARY KEY 是 rowid 的别名, 记录中保存的是 NULL
+        let alias = match primary.as_slice() {
+            [(index, true)] => Some(*index),
+            _ => None,
+        };
+        let mut names: Vec<String> = columns.iter().map(|name| quote(name)).collect();
+        if alias.is_none() {
+            names.insert(0, "rowid".to_string());
+        }
+        let sql = format!(
+            "INSERT OR IGNORE INTO {} ({}) VALUES ({})",
+            quote(&table.name),
+            names.join(", "),
+            vec!["?"; names.len()].join(", ")
+        );
+        let mut count = 0;
+        let mut transaction = connection.begin().await?;
+        for (rowid, mut values) in scanner.table_records(&table, columns.len()) {
+            values.resize(columns.len(), SqlValue::Null);
+            match alias {
+                Some(index) => values[index] = SqlValue::Integer(rowid),
+                None => values.insert(0, SqlValue::Integer(rowid)),
+            }
+            let mut query = sqlx::query::<_>(&sql);
+            for value in values {
+                query = bind_value(query, value);
+            }
+            count += query.execute(&mut *transaction).await?.rows_affected();
+        }
+        transaction.commit().await?;
+        connection.close().await?;
+        Ok(Some(count))
+    }
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn bind_value<'q>(
+    query: sqlx::query::Query<'q, sqlx::Sqlite, SqliteArguments<'q>>,
+    value: SqlValue,
+) -> sqlx::query::Query<'q, sqlx::Sqlite, SqliteArguments<'q>> {
+    match value {
+        SqlValue::Null => query.bind(None::<i64>),
+        SqlValue::Integer(v) => query.bind(v),
+        SqlValue::Real(v) => query.bind(v),
+        SqlValue::Text(v) => query.bind(v),
+        SqlValue::Blob(v) => query.bind(v),
+    }
+}
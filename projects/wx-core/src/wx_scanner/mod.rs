@@ -1,11 +1,22 @@
-use std::fmt::{Debug, Formatter};
+use crate::{WxError, WxKeyChecker, WxResult};
+use byteorder::{LittleEndian, ReadBytesExt};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Formatter},
+    fs::File,
+    io::Read,
+    path::Path,
+};
+use tracing::{debug, error, warn};
 
-#[cfg(windows)]
-mod on_windows;
-#[cfg(target_os = "macos")]
-mod on_macos;
+mod on_dump;
 #[cfg(target_os = "linux")]
 mod on_linux;
+#[cfg(target_os = "macos")]
+mod on_macos;
+#[cfg(windows)]
+mod on_windows;
 
 /// 微信个人数据
 #[derive(Default)]
@@ -42,12 +53,25 @@ pub struct WxScanner {
     pub profile: WeChatProfile,
 }
 
-
+/// 微信扫描器
 #[cfg(target_os = "linux")]
 #[derive(Debug, Default)]
 pub struct WxScanner {
     /// 微信个人数据
     pub profile: WeChatProfile,
+    process: on_linux::ProcessEntry,
+    module: on_linux::ModuleEntry,
+}
+
+/// 在内存中找到的秘钥
+#[derive(Clone, Copy, Debug)]
+pub struct WxKeyLocation {
+    /// 秘钥指针所在的地址
+    pub pointer: usize,
+    /// 秘钥所在的地址
+    pub address: usize,
+    /// 微信加密秘钥
+    pub aes256: [u8; 32],
 }
 
 impl Debug for WeChatProfile {
@@ -63,3 +87,215 @@ impl Debug for WeChatProfile {
             .finish()
     }
 }
+
+pub use self::on_dump::WxMemoryDump;
+
+/// 微信内存数据源, 可以是运行中的微信进程, 也可以是内存转储文件
+pub trait WxMemory {
+    /// 微信个人数据
+    fn profile(&self) -> &WeChatProfile;
+    /// 读取内存
+    ///
+    /// `real_addr` 为假时 `index` 是相对于微信模块基址的偏移量
+    fn read_memory(&self, index: usize, len: usize, real_addr: bool) -> WxResult<Vec<u8>>;
+    /// 在微信模块中搜索数据
+    ///
+    /// `real` 为假时返回相对于微信模块基址的偏移量
+    fn memory_search(&self, bytes: &[u8], real: bool) -> WxResult<Vec<usize>>;
+    /// 所有可读的内存区域, 以 `(基址, 长度)` 表示
+    fn memory_regions(&self) -> WxResult<Vec<(usize, usize)>>;
+    /// 微信模块的版本号
+    fn version(&self) -> WxResult<String>;
+    /// 在所有可读的内存区域中搜索数据, 返回真实地址
+    fn search_in_all_regions(&self, bytes: &[u8]) -> WxResult<Vec<usize>> {
+        let mut result = vec![];
+        for (base_addr, size) in self.memory_regions()? {
+            if let Ok(vec) = self.read_memory(base_addr, size, true) {
+                result.extend(search_bytes(&vec, bytes).map(|i| base_addr + i));
+            }
+        }
+        Ok(result)
+    }
+    /// 不依赖偏移量, 在所有可读的内存区域中寻找能解密数据库的秘钥
+    ///
+    /// 秘钥以 `(指针, 长度 32)` 的形式保存, 只有指向可读内存的候选才会用数据库校验
+    fn find_key(&self, checker: &WxKeyChecker) -> WxResult<Option<WxKeyLocation>> {
+        let regions = self.memory_regions()?;
+        let readable = |address: usize| {
+            let Some(end) = address.checked_add(32)
+            else {
+                return false;
+            };
+            regions.iter().any(|(start, size)| *start <= address && start.checked_add(*size).is_some_and(|stop| end <= stop))
+        };
+        let mut tried = HashSet::new();
+        for (base_addr, size) in &regions {
+            let Ok(vec) = self.read_memory(*base_addr, *size, true)
+            else {
+                continue;
+            };
+            for i in (0..vec.len().saturating_sub(15)).step_by(8) {
+                let address = u64::from_le_bytes(vec[i..i + 8].try_into()?) as usize;
+                let length = u64::from_le_bytes(vec[i + 8..i + 16].try_into()?);
+                if length != 32 || address == 0 || !readable(address) {
+                    continue;
+                }
+                let Ok(key) = self.read_memory(address, 32, true)
+                else {
+                    continue;
+                };
+                let aes256: [u8; 32] = key[..].try_into()?;
+                if !looks_like_key(&aes256) || !tried.insert(aes256) {
+                    continue;
+                }
+                if checker.check(&aes256) {
+                    return Ok(Some(WxKeyLocation { pointer: base_addr + i, address, aes256 }));
+                }
+            }
+        }
+        debug!("共校验了 {} 个候选秘钥", tried.len());
+        Ok(None)
+    }
+    /// 根据已知的个人数据和数据库推导当前版本的偏移量
+    ///
+    /// `known` 中为空的字段不参与搜索, 偏移量记为 0; 同一个值出现多次时, 取离秘钥指针最近的一处
+    fn discover_offsets(&self, known: &WeChatProfile, checker: &WxKeyChecker) -> WxResult<Vec<usize>> {
+        let found = self.find_key(checker)?.ok_or(WxError::custom("未找到能够解密数据库的秘钥"))?;
+        let mut pointers = vec![];
+        for address in self.search_in_all_regions(&found.aes256)? {
+            pointers.extend(self.memory_search(&(address as u64).to_le_bytes(), false)?);
+        }
+        // 秘钥指针后面紧跟着秘钥长度
+        let key_offset = match pointers
+            .iter()
+            .find(|offset| matches!(self.read_memory(**offset + 8, 8, false), Ok(v) if v[..] == 32u64.to_le_bytes()))
+        {
+            Some(s) => *s,
+            None => *pointers.first().ok_or(WxError::custom("微信模块中未找到秘钥指针"))?,
+        };
+        let mut offsets = vec![];
+        for value in [&known.nick_name, &known.user_name, &known.mobile, &known.email] {
+            if value.is_empty() {
+                offsets.push(0);
+                continue;
+            }
+            let needle = [value.as_bytes(), &[0]].concat();
+            match self.memory_search(&needle, false)?.into_iter().min_by_key(|offset| offset.abs_diff(key_offset)) {
+                Some(s) => offsets.push(s),
+                None => {
+                    warn!("微信模块中未找到: {}", value);
+                    offsets.push(0)
+                }
+            }
+        }
+        offsets.push(key_offset);
+        Ok(offsets)
+    }
+}
+
+/// 秘钥是随机生成的, 重复字节过多的数据不可能是秘钥
+fn looks_like_key(key: &[u8; 32]) -> bool {
+    key.iter().collect::<HashSet<_>>().len() >= 16
+}
+
+/// 按版本偏移量读取个人数据和秘钥
+///
+/// 偏移量依次为昵称, 账号, 手机号, 邮箱和秘钥指针
+fn read_profile<M: WxMemory + ?Sized>(memory: &M, version: &str, offset_map: &Option<String>) -> WxResult<WeChatProfile> {
+    let offset_map_map = load_offset_map(offset_map)?;
+    let offsets =
+        offset_map_map.get(version).ok_or(WxError::custom(format!("微信版本为：{}，未找到该版本的偏移量", version)))?;
+    let mut profile = WeChatProfile { version: version.to_string(), ..Default::default() };
+    let read_string = |offset: Option<&usize>, field: &str| -> WxResult<String> {
+        let offset = match offset {
+            Some(0) | None => return Err(WxError::unsupported_offset(version, field)),
+            Some(s) => *s,
+        };
+        let buffer = memory.read_memory(offset, 128, false)?;
+        Ok(String::from_utf8_lossy(buffer.split(|n| *n == 0).next().unwrap()).to_string())
+    };
+    match read_string(offsets.first(), "nick_name") {
+        Ok(s) => profile.nick_name = s,
+        Err(e) => error!("{}", e),
+    };
+    match read_string(offsets.get(1), "account") {
+        Ok(s) => profile.user_name = s,
+        Err(e) => error!("{}", e),
+    };
+    match read_string(offsets.get(2), "phone") {
+        Ok(s) => profile.mobile = s,
+        Err(e) => error!("{}", e),
+    };
+    match read_string(offsets.get(3), "email") {
+        Ok(s) => profile.email = s,
+        Err(e) => error!("{}", e),
+    };
+    let offset = *offsets.get(4).ok_or(WxError::unsupported_offset(version, "key"))?;
+    profile.aes256 = read_wechat_key(memory, offset)?;
+    Ok(profile)
+}
+
+/// 通过模块中的秘钥指针读取 32 字节的秘钥
+fn read_wechat_key<M: WxMemory + ?Sized>(memory: &M, offset: usize) -> WxResult<[u8; 32]> {
+    let buffer = memory.read_memory(offset, 8, false)?;
+    let mut cur = std::io::Cursor::new(&buffer);
+    let address = cur.read_u64::<LittleEndian>()?;
+    let key_buffer = memory.read_memory(address as usize, 32, true)?;
+    Ok(key_buffer[..].try_into()?)
+}
+
+fn search_bytes<'a>(haystack: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    haystack.windows(needle.len().max(1)).enumerate().filter(move |(_, w)| *w == needle).map(|(i, _)| i)
+}
+
+/// 从 PE 映像的 `VS_FIXEDFILEINFO` 中读取版本号
+///
+/// 文件和加载到内存中的映像都带有这个结构
+fn parse_file_version(image: &[u8]) -> Option<String> {
+    // VS_FIXEDFILEINFO.dwSignature
+    let signature = 0xFEEF04BDu32.to_le_bytes();
+    let index = search_bytes(image, &signature).next()?;
+    let mut cur = std::io::Cursor::new(&image[index..]);
+    cur.set_position(8);
+    let ms = cur.read_u32::<LittleEndian>().ok()?;
+    let ls = cur.read_u32::<LittleEndian>().ok()?;
+    Some(format!("{}.{}.{}.{}", ms >> 16, ms & 0xffff, ls >> 16, ls & 0xffff))
+}
+
+/// 把新版本的偏移量写入映射文件, 文件不存在时以内置映射为基础
+///
+/// 已有的条目保持原来的顺序, 新版本追加在末尾
+pub fn save_offset_map(path: &Path, version: &str, offsets: &[usize]) -> WxResult<()> {
+    let mut map: serde_json::Map<String, serde_json::Value> = match path.exists() {
+        true => serde_json::de::from_str(&std::fs::read_to_string(path)?)?,
+        false => serde_json::de::from_str(include_str!("on_windows.json"))?,
+    };
+    map.insert(version.to_string(), offsets.into());
+    let mut buffer = vec![];
+    let mut serializer =
+        serde_json::Serializer::with_formatter(&mut buffer, serde_json::ser::PrettyFormatter::with_indent(b"    "));
+    map.serialize(&mut serializer)?;
+    std::fs::write(path, buffer)?;
+    Ok(())
+}
+
+/// 读取版本偏移量映射, 未指定或无法打开时使用内置映射
+fn load_offset_map(offset_map: &Option<String>) -> WxResult<HashMap<String, Vec<usize>>> {
+    let mut buf = String::new();
+    match offset_map {
+        Some(s) => match File::open(s) {
+            Ok(mut o) => {
+                o.read_to_string(&mut buf)?;
+            }
+            Err(_) => {
+                warn!("无法找到 `on_windows.json` 配置, 使用内置映射");
+                buf.push_str(include_str!("on_windows.json"))
+            }
+        },
+        None => {
+            tracing::info!("未配置 `on_windows.json` ,使用内置映射");
+            buf.push_str(include_str!("on_windows.json"))
+        }
+    }
+    Ok(serde_json::de::from_str(&buf)?)
+}
@@ -0,0 +1,366 @@
+use super::*;
+
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// minidump 文件头 `MDMP`
+const MINIDUMP_SIGNATURE: &[u8; 4] = b"MDMP";
+/// ELF 文件头
+const ELF_SIGNATURE: &[u8; 4] = b"\x7FELF";
+
+/// 从内存转储文件中读取微信数据
+///
+/// 支持 Windows 的完整内存 minidump 和 Linux 的 ELF core 文件, 无需运行中的微信进程
+#[derive(Debug, Default)]
+pub struct WxMemoryDump {
+    /// 微信个人数据
+    pub profile: WeChatProfile,
+    file: Option<File>,
+    regions: Vec<DumpRegion>,
+    modules: Vec<DumpModule>,
+    module: DumpModule,
+}
+
+/// 转储文件中保存的一段内存
+#[derive(Clone, Debug, Default)]
+struct DumpRegion {
+    start: usize,
+    size: usize,
+    file_offset: u64,
+}
+
+/// 转储文件中记录的模块
+#[derive(Clone, Debug, Default)]
+struct DumpModule {
+    name: String,
+    base: usize,
+    size: usize,
+    version: Option<String>,
+}
+
+impl WxMemory for WxMemoryDump {
+    fn profile(&self) -> &WeChatProfile {
+        &self.profile
+    }
+    fn read_memory(&self, index: usize, len: usize, real_addr: bool) -> WxResult<Vec<u8>> {
+        if real_addr {
+            self.read_memory_data(index, len)
+        }
+        else {
+            let address = self.module.base.checked_add(index).ok_or(WxError::custom(format!("地址溢出: {:#x}", index)))?;
+            self.read_memory_data(address, len)
+        }
+    }
+    fn memory_search(&self, bytes: &[u8], real: bool) -> WxResult<Vec<usize>> {
+        let mut result = vec![];
+        for (start, length) in self.module_regions() {
+            let vec = self.read_memory_data(start, length)?;
+            let offset = if real { start } else { start - self.module.base };
+            result.extend(search_bytes(&vec, bytes).map(|i| offset + i));
+        }
+        Ok(result)
+    }
+    fn memory_regions(&self) -> WxResult<Vec<(usize, usize)>> {
+        Ok(self.regions.iter().map(|region| (region.start, region.size)).collect())
+    }
+    fn version(&self) -> WxResult<String> {
+        if let Some(s) = &self.module.version {
+            return Ok(s.clone());
+        }
+        let version = match self.resource_range() {
+            Ok((rva, size)) => parse_file_version(&self.read_memory(rva, size, false)?),
+            Err(e) => {
+                // 头部没有被转储时, 只在转储文件保存的模块范围内查找
+                debug!("无法读取 {} 的资源段: {}", self.module.name, e);
+                self.module_regions().find_map(|(start, length)| {
+                    self.read_memory_data(start, length).ok().and_then(|data| parse_file_version(&data))
+                })
+            }
+        };
+        version.ok_or(WxError::custom(format!("无法读取 {} 的版本信息", self.module.name)))
+    }
+}
+
+impl WxMemoryDump {
+    /// 打开内存转储文件, 并按偏移量读取个人数据和秘钥
+    pub fn open_wechat_dump(&mut self, dump_path: &Path, offset_map: &Option<String>, module_name: &str) -> WxResult<()> {
+        self.open_wechat_dump_with_out_info(dump_path, module_name)?;
+        let version = self.version()?;
+        self.profile = read_profile(self, &version, offset_map)?;
+        Ok(())
+    }
+    /// 打开内存转储文件, 不读取个人数据
+    pub fn open_wechat_dump_with_out_info(&mut self, dump_path: &Path, module_name: &str) -> WxResult<()> {
+        let file = File::open(dump_path)?;
+        let magic = read_file_at(&file, 0, 4)?;
+        let (regions, modules) = if magic == MINIDUMP_SIGNATURE {
+            parse_minidump(&file)?
+        }
+        else if magic == ELF_SIGNATURE {
+            parse_elf_core(&file)?
+        }
+        else {
+            return Err(WxError::custom(format!("无法识别的内存转储文件: {}", dump_path.display())));
+        };
+        self.module = modules
+            .iter()
+            .find(|module| module.name.eq_ignore_ascii_case(module_name))
+            .cloned()
+            .ok_or(WxError::custom(format!("转储文件中未找到模块: {}", module_name)))?;
+        self.file = Some(file);
+        self.regions = regions;
+        self.modules = modules;
+        Ok(())
+    }
+    /// 转储文件中记录的所有模块, 以 `(模块名, 基址, 长度)` 表示
+    pub fn modules(&self) -> Vec<(&str, usize, usize)> {
+        self.modules.iter().map(|module| (module.name.as_str(), module.base, module.size)).collect()
+    }
+    /// 转储文件中保存的模块范围, 以 `(真实地址, 长度)` 表示
+    fn module_regions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let (base, end) = (self.module.base, self.module.base.saturating_add(self.module.size));
+        self.regions.iter().filter_map(move |region| {
+            let start = region.start.max(base);
+            let stop = region.end().min(end);
+            (start < stop).then(|| (start, stop - start))
+        })
+    }
+    /// 从模块的 PE 头中读取资源段的位置, 以 `(相对地址, 长度)` 表示
+    fn resource_range(&self) -> WxResult<(usize, usize)> {
+        let dos = self.read_memory(0, 0x40, false)?;
+        if &dos[0..2] != b"MZ" {
+            return Err(WxError::custom("模块不是 PE 映像"));
+        }
+        let pe = Cursor::new(&dos[0x3C..]).read_u32::<LittleEndian>()? as usize;
+        let mut header = Cursor::new(self.read_memory(pe, 24, false)?);
+        if &header.get_ref()[0..4] != b"PE\0\0" {
+            return Err(WxError::custom("模块不是 PE 映像"));
+        }
+        header.set_position(20);
+        let optional_size = header.read_u16::<LittleEndian>()? as usize;
+        let optional = self.read_memory(pe + 24, optional_size, false)?;
+        // IMAGE_OPTIONAL_HEADER 中数据目录的位置, 资源目录是第三项
+        let directories = match Cursor::new(&optional).read_u16::<LittleEndian>()? {
+            0x10B => 96,
+            0x20B => 112,
+            _ => return Err(WxError::custom("无法识别的 PE 可选头")),
+        };
+        let mut cur =
+            Cursor::new(optional.get(directories + 16..directories + 24).ok_or(WxError::custom("PE 头中没有资源目录"))?);
+        let rva = cur.read_u32::<LittleEndian>()? as usize;
+        let size = cur.read_u32::<LittleEndian>()? as usize;
+        match rva.checked_add(size) {
+            Some(end) if size > 0 && end <= self.module.size => Ok((rva, size)),
+            _ => Err(WxError::custom(format!("资源目录超出模块范围: {:#x}+{:#x}", rva, size))),
+        }
+    }
+    /// 读取真实地址上的数据, 允许跨越相邻的内存段
+    fn read_memory_data(&self, mut address: usize, length: usize) -> WxResult<Vec<u8>> {
+        let file = self.file.as_ref().ok_or(WxError::custom("尚未打开内存转储文件"))?;
+        let mut vec = Vec::with_capacity(length);
+        while vec.len() < length {
+            let region = self
+                .regions
+                .iter()
+                .find(|region| region.start <= address && address < region.end())
+                .ok_or(WxError::custom(format!("地址 {:#x} 不在转储文件中", address)))?;
+            let count = (length - vec.len()).min(region.end() - address);
+            vec.extend(read_file_at(file, region.file_offset + (address - region.start) as u64, count)?);
+            address += count;
+        }
+        Ok(vec)
+    }
+}
+
+impl DumpRegion {
+    /// 检查内存段没有溢出, 并且完整地保存在转储文件中
+    fn new(start: usize, size: u64, file_offset: u64, file_length: u64) -> WxResult<Self> {
+        let size = usize::try_from(size).ok().filter(|size| start.checked_add(*size).is_some());
+        match size {
+            Some(size) if file_offset.checked_add(size as u64).is_some_and(|end| end <= file_length) => {
+                Ok(Self { start, size, file_offset })
+            }
+            _ => Err(WxError::custom(format!("内存段 {:#x} 超出转储文件范围", start))),
+        }
+    }
+    /// 内存段的结束地址, 创建时已经检查过不会溢出
+    fn end(&self) -> usize {
+        self.start + self.size
+    }
+}
+
+/// 读取文件中的一段数据, 超出文件范围时返回错误, 不会按损坏的长度分配内存
+fn read_file_at(mut file: &File, offset: u64, length: usize) -> WxResult<Vec<u8>> {
+    let file_length = file.metadata()?.len();
+    if offset.checked_add(length as u64).is_none_or(|end| end > file_length) {
+        return Err(WxError::custom(format!("读取 {:#x} 处的 {} 字节超出转储文件范围", offset, length)));
+    }
+    let mut buffer = vec![0u8; length];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// 解析 minidump 的 `ModuleListStream`, `MemoryListStream` 和 `Memory64ListStream`
+fn parse_minidump(file: &File) -> WxResult<(Vec<DumpRegion>, Vec<DumpModule>)> {
+    const MODULE_LIST_STREAM: u32 = 4;
+    const MEMORY_LIST_STREAM: u32 = 5;
+    const MEMORY64_LIST_STREAM: u32 = 9;
+    let file_length = file.metadata()?.len();
+    let mut header = Cursor::new(read_file_at(file, 0, 32)?);
+    header.set_position(8);
+    let streams = header.read_u32::<LittleEndian>()?;
+    let directory = header.read_u32::<LittleEndian>()?;
+    let length = (streams as usize).checked_mul(12).ok_or(WxError::custom("minidump 的流数量无效"))?;
+    let mut directory = Cursor::new(read_file_at(file, directory as u64, length)?);
+    let mut regions = vec![];
+    let mut modules = vec![];
+    for _ in 0..streams {
+        let stream_type = directory.read_u32::<LittleEndian>()?;
+        let size = directory.read_u32::<LittleEndian>()?;
+        let rva = directory.read_u32::<LittleEndian>()? as u64;
+        match stream_type {
+            MODULE_LIST_STREAM => {
+                let mut cur = Cursor::new(read_file_at(file, rva, size as usize)?);
+                let count = cur.read_u32::<LittleEndian>()?;
+                for _ in 0..count {
+                    // MINIDUMP_MODULE, 108 bytes
+                    let entry = cur.position();
+                    let base = cur.read_u64::<LittleEndian>()? as usize;
+                    let size = cur.read_u32::<LittleEndian>()? as usize;
+                    cur.set_position(entry + 20);
+                    let name_rva = cur.read_u32::<LittleEndian>()? as u64;
+                    let signature = cur.read_u32::<LittleEndian>()?;
+                    cur.set_position(entry + 32);
+                    let ms = cur.read_u32::<LittleEndian>()?;
+                    let ls = cur.read_u32::<LittleEndian>()?;
+                    cur.set_position(entry + 108);
+                    let version =
+                        (signature == 0xFEEF04BD).then(|| format!("{}.{}.{}.{}", ms >> 16, ms & 0xffff, ls >> 16, ls & 0xffff));
+                    let name_length = Cursor::new(read_file_at(file, name_rva, 4)?).read_u32::<LittleEndian>()?;
+                    let name_bytes = read_file_at(file, name_rva + 4, name_length as usize)?;
+                    let name_utf16: Vec<u16> = name_bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+                    let path = String::from_utf16_lossy(&name_utf16);
+                    let name = path.rsplit(['\\', '/']).next().unwrap_or_default().to_string();
+                    modules.push(DumpModule { name, base, size, version });
+                }
+            }
+            MEMORY_LIST_STREAM => {
+                let mut cur = Cursor::new(read_file_at(file, rva, size as usize)?);
+                let count = cur.read_u32::<LittleEndian>()?;
+                for _ in 0..count {
+                    let start = cur.read_u64::<LittleEndian>()? as usize;
+                    let size = cur.read_u32::<LittleEndian>()? as usize;
+                    let file_offset = cur.read_u32::<LittleEndian>()? as u64;
+                    regions.push(DumpRegion::new(start, size as u64, file_offset, file_length)?);
+                }
+            }
+            MEMORY64_LIST_STREAM => {
+                let mut cur = Cursor::new(read_file_at(file, rva, size as usize)?);
+                let count = cur.read_u64::<LittleEndian>()?;
+                let mut file_offset = cur.read_u64::<LittleEndian>()?;
+                for _ in 0..count {
+                    let start = cur.read_u64::<LittleEndian>()? as usize;
+                    let size = cur.read_u64::<LittleEndian>()?;
+                    regions.push(DumpRegion::new(start, size, file_offset, file_length)?);
+                    file_offset += size;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((regions, modules))
+}
+
+/// 解析 64 位小端 ELF core 文件的 `PT_LOAD` 段和 `NT_FILE` 注释
+fn parse_elf_core(file: &File) -> WxResult<(Vec<DumpRegion>, Vec<DumpModule>)> {
+    const ET_CORE: u16 = 4;
+    const PT_LOAD: u32 = 1;
+    const PT_NOTE: u32 = 4;
+    const NT_FILE: u32 = 0x46494c45;
+    let file_length = file.metadata()?.len();
+    let mut header = Cursor::new(read_file_at(file, 0, 64)?);
+    if header.get_ref()[4] != 2 || header.get_ref()[5] != 1 {
+        return Err(WxError::custom("仅支持 64 位小端的 ELF core 文件"));
+    }
+    header.set_position(16);
+    if header.read_u16::<LittleEndian>()? != ET_CORE {
+        return Err(WxError::custom("ELF 文件不是 core 文件"));
+    }
+    header.set_position(32);
+    let phoff = header.read_u64::<LittleEndian>()?;
+    header.set_position(54);
+    let phentsize = header.read_u16::<LittleEndian>()? as u64;
+    let phnum = header.read_u16::<LittleEndian>()? as u64;
+    let mut regions = vec![];
+    let mut modules: Vec<DumpModule> = vec![];
+    for index in 0..phnum {
+        let entry = phoff.checked_add(index * phentsize).ok_or(WxError::custom("ELF 程序头的位置溢出"))?;
+        let mut cur = Cursor::new(read_file_at(file, entry, 56)?);
+        let p_type = cur.read_u32::<LittleEndian>()?;
+        cur.set_position(8);
+        let p_offset = cur.read_u64::<LittleEndian>()?;
+        let p_vaddr = cur.read_u64::<LittleEndian>()? as usize;
+        cur.set_position(32);
+        let p_filesz = cur.read_u64::<LittleEndian>()?;
+        match p_type {
+            PT_LOAD if p_filesz > 0 => {
+                regions.push(DumpRegion::new(p_vaddr, p_filesz, p_offset, file_length)?);
+            }
+            PT_NOTE => {
+                let notes = read_file_at(file, p_offset, p_filesz as usize)?;
+                let mut cur = Cursor::new(&notes[..]);
+                while (cur.position() as usize) + 12 <= notes.len() {
+                    let name_size = cur.read_u32::<LittleEndian>()? as u64;
+                    let desc_size = cur.read_u32::<LittleEndian>()? as u64;
+                    let note_type = cur.read_u32::<LittleEndian>()?;
+                    let desc_start = cur.position() + name_size.next_multiple_of(4);
+                    let desc_end = desc_start + desc_size;
+                    if desc_end as usize > notes.len() {
+                        break;
+                    }
+                    if note_type == NT_FILE {
+                        parse_nt_file(&notes[desc_start as usize..desc_end as usize], &mut modules)?;
+                    }
+                    cur.set_position(desc_start + desc_size.next_multiple_of(4));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((regions, modules))
+}
+
+/// `NT_FILE` 按映射列出文件, 同一个文件的多个映射合并为一个模块
+fn parse_nt_file(desc: &[u8], modules: &mut Vec<DumpModule>) -> WxResult<()> {
+    let mut cur = Cursor::new(desc);
+    let count = cur.read_u64::<LittleEndian>()? as usize;
+    let _page_size = cur.read_u64::<LittleEndian>()?;
+    let mut ranges = Vec::with_capacity(count.min(desc.len() / 24));
+    for _ in 0..count {
+        let start = cur.read_u64::<LittleEndian>()? as usize;
+        let end = cur.read_u64::<LittleEndian>()? as usize;
+        let _file_offset = cur.read_u64::<LittleEndian>()?;
+        if end < start {
+            return Err(WxError::custom(format!("NT_FILE 中的映射无效: {:#x}-{:#x}", start, end)));
+        }
+        ranges.push((start, end));
+    }
+    let names = desc[cur.position() as usize..].split(|n| *n == 0);
+    for ((start, end), path) in ranges.into_iter().zip(names) {
+        let path = String::from_utf8_lossy(path);
+        let name = path.rsplit('/').next().unwrap_or_default().to_string();
+        match modules.iter_mut().find(|module| module.name == name) {
+            Some(module) => {
+                let stop = (module.base + module.size).max(end);
+                module.base = module.base.min(start);
+                module.size = stop - module.base;
+            }
+            None => modules.push(DumpModule { name, base: start, size: end - start, version: None }),
+        }
+    }
+    Ok(())
+}
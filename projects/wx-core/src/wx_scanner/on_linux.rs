@@ -0,0 +1,306 @@
+use super::*;
+use crate::{WxError, WxResult};
+
+use std::{
+    fs::{File, read, read_dir, read_to_string},
+    os::unix::fs::FileExt,
+    path::Path,
+};
+
+/// `/proc` 中的进程信息
+#[derive(Clone, Debug, Default)]
+pub struct ProcessEntry {
+    /// 进程号
+    pub pid: u32,
+    /// 进程名, 取自 `comm` 或 `cmdline` 的第一个参数
+    pub name: String,
+}
+
+/// `/proc/<pid>/maps` 中的一段内存映射
+#[derive(Clone, Debug, Default)]
+pub struct MemoryMap {
+    /// 起始地址
+    pub start: usize,
+    /// 结束地址
+    pub end: usize,
+    /// 是否可读
+    pub readable: bool,
+    /// 映射的文件路径, 匿名映射为空
+    pub path: String,
+}
+
+/// 由同一个文件映射出的模块
+#[derive(Clone, Debug, Default)]
+pub struct ModuleEntry {
+    /// 模块名
+    pub name: String,
+    /// 模块文件路径
+    pub path: String,
+    /// 模块基址
+    pub base: usize,
+    /// 模块跨越的长度
+    pub size: usize,
+    /// 模块中所有可读的内存段
+    pub regions: Vec<(usize, usize)>,
+}
+
+impl WxMemory for WxScanner {
+    fn profile(&self) -> &WeChatProfile {
+        &self.profile
+    }
+    fn read_memory(&self, index: usize, len: usize, real_addr: bool) -> WxResult<Vec<u8>> {
+        if real_addr {
+            read_memory_data(self.process.pid, index, len)
+        }
+        else {
+            read_memory_data(self.process.pid, self.module.base + index, len)
+        }
+    }
+    fn memory_search(&self, bytes: &[u8], real: bool) -> WxResult<Vec<usize>> {
+        let mut result = vec![];
+        for (start, size) in &self.module.regions {
+            // 保护页等无法读取的区域直接跳过
+            let vec = match read_memory_data(self.process.pid, *start, *size) {
+                Ok(o) => o,
+                Err(e) => {
+                    debug!("无法读取内存: {:#x}, {}", start, e);
+                    continue;
+                }
+            };
+            let base = if real { *start } else { *start - self.module.base };
+            result.extend(search_bytes(&vec, bytes).map(|i| base + i));
+        }
+        Ok(result)
+    }
+    fn memory_regions(&self) -> WxResult<Vec<(usize, usize)>> {
+        get_all_memory_by_pid(self.process.pid)
+    }
+    fn version(&self) -> WxResult<String> {
+        get_version(&self.module)
+    }
+}
+
+impl WxScanner {
+    /// 搜索所有微信进程的内存
+    pub fn search_in_all_wechat_modules(
+        &self,
+        data: &[u8],
+        absolute_address: bool,
+        show_no_found_info: bool,
+        show_error_info: bool,
+    ) -> WxResult<()> {
+        for module in get_modules(self.process.pid)? {
+            let mut found = vec![];
+            for (start, size) in &module.regions {
+                match read_memory_data(self.process.pid, *start, *size) {
+                    Ok(vec) => {
+                        let base = if absolute_address { *start } else { *start - module.base };
+                        found.extend(search_bytes(&vec, data).map(|i| base + i));
+                    }
+                    Err(err) => {
+                        if show_error_info {
+                            println!("获取内存失败。module: {}。err: {err:?}", module.name);
+                            println!("addr start: {:?},size: {:?},end: {:?}", start, size, start + size);
+                        }
+                    }
+                }
+            }
+            if !found.is_empty() {
+                println!("module: {}", module.name);
+                println!("{:?}", found);
+            }
+            else if show_no_found_info {
+                println!(
+                    "在 {} 中未找到想要搜索的数据。开始位置：{},结束位置：{}, 长度：{}",
+                    module.name,
+                    module.base,
+                    module.base + module.size,
+                    module.size
+                );
+            }
+        }
+        Ok(())
+    }
+    /// 搜索所有微信进程的内存
+    pub fn search_in_all_wechat_data(
+        &self,
+        data: &[u8],
+        real_addr: bool,
+        show_no_found_info: bool,
+        show_error_info: bool,
+    ) -> WxResult<()> {
+        for (base_addr, size) in get_all_memory_by_pid(self.process.pid)? {
+            match read_memory_data(self.process.pid, base_addr, size) {
+                Ok(vec) => {
+                    let r: Vec<usize> = search_bytes(&vec, data).map(|i| if real_addr { base_addr + i } else { i }).collect();
+                    if !r.is_empty() {
+                        println!("base_addr: {}", base_addr);
+                        println!("{:?}", r);
+                    }
+                    else if show_no_found_info {
+                        println!(
+                            "未找到想要搜索的数据。开始位置：{},结束位置：{}, 长度：{}, vec 长度：{}",
+                            base_addr,
+                            base_addr + size,
+                            size,
+                            vec.len()
+                        );
+                    }
+                }
+                Err(err) => {
+                    if show_error_info {
+                        println!("获取内存失败。base_addr: {base_addr}。 size: {size}, err: {err:?}");
+                    }
+                    continue;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// 打开微信进程
+    pub fn open_wechat_process(
+        &mut self,
+        offset_map: &Option<String>,
+        process_id: &Option<u32>,
+        process_name: &str,
+        module_name: &str,
+    ) -> WxResult<()> {
+        self.open_wechat_process_with_out_info(process_id, process_name, module_name)?;
+        let version = self.version()?;
+        self.profile = read_profile(self, &version, offset_map)?;
+        Ok(())
+    }
+
+    /// 获取微信进程信息
+    pub fn open_wechat_process_with_out_info(
+        &mut self,
+        process_id: &Option<u32>,
+        process_name: &str,
+        module_name: &str,
+    ) -> WxResult<()> {
+        self.process = match process_id {
+            Some(id) => get_process_by_id(*id)?,
+            _ => get_process_by_name(process_name)?,
+        };
+        self.module = get_module_by_name(&self.process, module_name)?;
+        Ok(())
+    }
+}
+
+/// 从模块文件的 `VS_FIXEDFILEINFO` 中读取版本号
+///
+/// 在 Wine 中运行的 `WeChatWin.dll` 会以原始文件的形式出现在 `/proc/<pid>/maps` 中
+pub fn get_version(module: &ModuleEntry) -> WxResult<String> {
+    parse_file_version(&read(&module.path)?).ok_or(WxError::custom(format!("无法读取 {} 的版本信息", module.path)))
+}
+
+/// 读取 `/proc/<pid>/maps` 中所有的内存映射
+pub fn get_memory_maps(process_id: u32) -> WxResult<Vec<MemoryMap>> {
+    let maps = read_to_string(format!("/proc/{}/maps", process_id))?;
+    let mut vec = vec![];
+    for line in maps.lines() {
+        // address perms offset dev inode pathname
+        let mut parts = line.splitn(6, ' ');
+        let (Some(range), Some(perms)) = (parts.next(), parts.next())
+        else {
+            continue;
+        };
+        let Some((start, end)) = range.split_once('-')
+        else {
+            continue;
+        };
+        vec.push(MemoryMap {
+            start: usize::from_str_radix(start, 16)?,
+            end: usize::from_str_radix(end, 16)?,
+            readable: perms.starts_with('r'),
+            path: parts.nth(3).unwrap_or_default().trim_start().to_string(),
+        })
+    }
+    Ok(vec)
+}
+
+/// 获取进程中所有可读的内存区域
+pub fn get_all_memory_by_pid(process_id: u32) -> WxResult<Vec<(usize, usize)>> {
+    Ok(get_memory_maps(process_id)?
+        .into_iter()
+        .filter(|map| map.readable && map.path != "[vvar]" && map.path != "[vsyscall]")
+        .map(|map| (map.start, map.end - map.start))
+        .collect())
+}
+
+/// 读取实际内存中的数据
+pub fn read_memory_data(process_id: u32, offset: usize, length: usize) -> WxResult<Vec<u8>> {
+    let memory = File::open(format!("/proc/{}/mem", process_id))?;
+    let mut vec = vec![0u8; length];
+    memory.read_exact_at(&mut vec, offset as u64)?;
+    Ok(vec)
+}
+
+/// 读取进程信息, 同时返回 `comm` 中的进程名
+fn read_process_entry(process_id: u32) -> Option<(ProcessEntry, String)> {
+    let comm = read_to_string(format!("/proc/{}/comm", process_id)).ok()?;
+    let cmdline = read(format!("/proc/{}/cmdline", process_id)).unwrap_or_default();
+    let argv0 = String::from_utf8_lossy(cmdline.split(|n| *n == 0).next().unwrap_or_default()).to_string();
+    // Wine 下的 argv0 形如 `C:\Program Files\Tencent\WeChat\WeChat.exe`
+    let comm = comm.trim_end().to_string();
+    let name = match argv0.rsplit(['/', '\\']).next() {
+        Some(s) if !s.is_empty() => s.to_string(),
+        _ => comm.clone(),
+    };
+    Some((ProcessEntry { pid: process_id, name }, comm))
+}
+
+/// 按进程名查找进程, 同时匹配 `comm` 和 `cmdline`
+pub fn get_process_by_name(process_name: &str) -> WxResult<ProcessEntry> {
+    for entity in read_dir("/proc")? {
+        let Some(pid) = entity?.file_name().to_str().and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Some((process, comm)) = read_process_entry(pid)
+        else {
+            continue;
+        };
+        if process.name == process_name || comm == process_name {
+            return Ok(process);
+        }
+    }
+    Err(WxError::custom(format!("未找到进程: {}", process_name)))
+}
+
+/// 按进程号查找进程
+pub fn get_process_by_id(process_id: u32) -> WxResult<ProcessEntry> {
+    read_process_entry(process_id).map(|(process, _)| process).ok_or(WxError::custom(format!("未找到进程: {}", process_id)))
+}
+
+/// 按模块名查找模块
+pub fn get_module_by_name(process: &ProcessEntry, module_name: &str) -> WxResult<ModuleEntry> {
+    get_modules(process.pid)?
+        .into_iter()
+        .find(|module| module.name == module_name)
+        .ok_or(WxError::custom(format!("进程 {} 中未找到模块: {}", process.pid, module_name)))
+}
+
+/// 列出进程中所有由文件映射出的模块
+pub fn get_modules(process_id: u32) -> WxResult<Vec<ModuleEntry>> {
+    let mut vec: Vec<ModuleEntry> = vec![];
+    for map in get_memory_maps(process_id)? {
+        if map.path.is_empty() || map.path.starts_with('[') {
+            continue;
+        }
+        let module = match vec.iter_mut().find(|module| module.path == map.path) {
+            Some(s) => s,
+            None => {
+                let name = Path::new(&map.path).file_name().and_then(|s| s.to_str()).unwrap_or(&map.path).to_string();
+                vec.push(ModuleEntry { name, path: map.path.clone(), base: map.start, ..Default::default() });
+                vec.last_mut().unwrap()
+            }
+        };
+        module.base = module.base.min(map.start);
+        module.size = module.size.max(map.end - module.base);
+        if map.readable {
+            module.regions.push((map.start, map.end - map.start));
+        }
+    }
+    Ok(vec)
+}
@@ -1,9 +1,7 @@
 use super::*;
-use crate::{WxError, WxResult};
+use crate::WxResult;
 
-use byteorder::{ LittleEndian, ReadBytesExt};
-use std::{ collections::HashMap, ffi::c_void, fs::File, io::Read};
-use tracing::{error, info};
+use std::ffi::c_void;
 use windows::{
     Win32::{
         Foundation::{GetLastError, HANDLE},
@@ -14,7 +12,7 @@ use windows::{
                 TH32CS_SNAPPROCESS, Toolhelp32ReadProcessMemory,
             },
             LibraryLoader::{FindResourceA, LOAD_LIBRARY_AS_DATAFILE, LoadLibraryExA, LoadResource},
-            Memory::{MEMORY_BASIC_INFORMATION, VirtualQueryEx},
+            Memory::{MEM_COMMIT, MEMORY_BASIC_INFORMATION, PAGE_GUARD, PAGE_NOACCESS, VirtualQueryEx},
             Threading::{OpenProcess, PROCESS_ALL_ACCESS},
         },
         UI::WindowsAndMessaging::RT_VERSION,
@@ -22,26 +20,11 @@ use windows::{
     core::PCSTR,
 };
 
-impl WxScanner {
-    fn read_version(&mut self) -> WxResult<String> {
-        get_version(&self.module)
-    }
-
-    fn read_string(&mut self, offset: Option<&usize>, field: &str) -> WxResult<String> {
-        let offset = match offset {
-            Some(0) => {
-                return Err(WxError::unsupported_offset(&self.profile.version, field));
-            }
-            Some(s) => *s,
-            None => {
-                return Err(WxError::unsupported_offset(&self.profile.version, field));
-            }
-        };
-        let buffer = read_memory_data(self.process.th32ProcessID, self.module.modBaseAddr as usize + offset, 128)?;
-        Ok(String::from_utf8_lossy(buffer.split(|n| *n == 0).next().unwrap()).to_string())
+impl WxMemory for WxScanner {
+    fn profile(&self) -> &WeChatProfile {
+        &self.profile
     }
-    /// 读取内存
-    pub fn read_memory(&self, index: usize, len: usize, real_addr: bool) -> WxResult<Vec<u8>> {
+    fn read_memory(&self, index: usize, len: usize, real_addr: bool) -> WxResult<Vec<u8>> {
         let process = self.process;
         let vec = if real_addr {
             read_memory_data(process.th32ProcessID, index, len)?
@@ -51,16 +34,22 @@ impl WxScanner {
         };
         Ok(vec)
     }
-    pub fn memory_search(&self, bytes: &[u8], real: bool) -> WxResult<Vec<usize>> {
+    fn memory_search(&self, bytes: &[u8], real: bool) -> WxResult<Vec<usize>> {
         let process = self.process;
         let module = self.module;
         let vec = read_memory_data(process.th32ProcessID, module.modBaseAddr as usize, module.modBaseSize as usize)?;
-        let r = (0..vec.len() - bytes.len())
-            .filter(|&i| &vec[i..i + bytes.len()] == bytes)
-            .map(|i| if real { module.modBaseAddr as usize + i } else { i })
-            .collect();
+        let r = search_bytes(&vec, bytes).map(|i| if real { module.modBaseAddr as usize + i } else { i }).collect();
         Ok(r)
     }
+    fn memory_regions(&self) -> WxResult<Vec<(usize, usize)>> {
+        get_all_memory_by_handle(&self.handle)
+    }
+    fn version(&self) -> WxResult<String> {
+        get_version(&self.module)
+    }
+}
+
+impl WxScanner {
     /// 搜索所有微信进程的内存
     pub fn search_in_all_wechat_modules(
         &self,
@@ -170,55 +159,17 @@ impl WxScanner {
         &mut self,
         offset_map: &Option<String>,
         process_id: &Option<u32>,
-        process_name: &String,
-        module_name: &String,
+        process_name: &str,
+        module_name: &str,
     ) -> WxResult<()> {
         self.process = match process_id {
             Some(id) => get_process_by_id(*id)?,
-            _ => get_process_by_name(&process_name)?,
+            _ => get_process_by_name(process_name)?,
         };
         self.handle = get_process_handle(self.process.th32ProcessID)?;
-        self.module = get_module_by_name(&self.process, &module_name)?;
-        self.profile.version = self.read_version()?;
-        let mut buf = String::new();
-        match offset_map {
-            Some(s) => {
-                match File::open(s) {
-                    Ok(mut o) => {
-                        o.read_to_string(&mut buf)?;
-                    }
-                    Err(_) => {
-                        println!("无法找到 `on_windows.json` 配置, 使用内置映射");
-                        buf.push_str(include_str!("on_windows.json"))
-                    }
-                };
-            }
-            None => {
-                info!("未配置 `on_windows.json` ,使用内置映射")
-            }
-        }
-
-        let offset_map_map: HashMap<String, Vec<usize>> = serde_json::de::from_str(&buf)?;
-        let offsets = offset_map_map
-            .get(&self.profile.version)
-            .ok_or(WxError::custom(format!("微信版本为：{}，未找到该版本的偏移量", self.profile.version)))?;
-        match self.read_string(offsets.get(0), "nick_name") {
-            Ok(s) => self.profile.nick_name = s,
-            Err(e) => error!("{}", e),
-        };
-        match self.read_string(offsets.get(1), "account") {
-            Ok(s) => self.profile.user_name = s,
-            Err(e) => error!("{}", e),
-        };
-        match self.read_string(offsets.get(2), "phone") {
-            Ok(s) => self.profile.mobile = s,
-            Err(e) => error!("{}", e),
-        };
-        match self.read_string(offsets.get(3), "email") {
-            Ok(s) => self.profile.email = s,
-            Err(e) => error!("{}", e),
-        };
-        self.profile.aes256 = read_wechat_key(self, offsets[4])?;
+        self.module = get_module_by_name(&self.process, module_name)?;
+        let version = self.version()?;
+        self.profile = read_profile(self, &version, offset_map)?;
         Ok(())
     }
 
@@ -226,28 +177,19 @@ impl WxScanner {
     pub fn open_wechat_process_with_out_info(
         &mut self,
         process_id: &Option<u32>,
-        process_name: &String,
-        module_name: &String,
+        process_name: &str,
+        module_name: &str,
     ) -> WxResult<()> {
         self.process = match process_id {
             Some(id) => get_process_by_id(*id)?,
-            _ => get_process_by_name(&process_name)?,
+            _ => get_process_by_name(process_name)?,
         };
         self.handle = get_process_handle(self.process.th32ProcessID)?;
-        self.module = get_module_by_name(&self.process, &module_name)?;
+        self.module = get_module_by_name(&self.process, module_name)?;
         Ok(())
     }
 }
 
-fn read_wechat_key(wechat_info: &mut WxScanner, offset: usize) -> WxResult<[u8; 32]> {
-    let process = wechat_info.process;
-    let buffer = read_memory_data(process.th32ProcessID, wechat_info.module.modBaseAddr as usize + offset, 8)?;
-    let mut cur = std::io::Cursor::new(&buffer);
-    let offset = cur.read_u64::<LittleEndian>()?;
-    let key_buffer = read_memory_data(process.th32ProcessID, offset as usize, 32)?;
-    Ok(*&key_buffer[..].try_into()?)
-}
-
 pub fn get_version(module: &MODULEENTRY32) -> WxResult<String> {
     unsafe {
         let image = LoadLibraryExA(
@@ -277,6 +219,7 @@ pub fn get_version(module: &MODULEENTRY32) -> WxResult<String> {
     }
 }
 
+/// 获取进程中所有已提交并且可读的内存区域
 pub fn get_all_memory_by_handle(handle: &HANDLE) -> WxResult<Vec<(usize, usize)>> {
     let mut lp_addr = None;
     let mut vec = vec![];
@@ -292,6 +235,10 @@ pub fn get_all_memory_by_handle(handle: &HANDLE) -> WxResult<Vec<(usize, usize)>
         let base_addr = memory_basic_info.BaseAddress as usize;
         let size = memory_basic_info.RegionSize as usize;
         lp_addr = Some((base_addr + size) as *const c_void);
+        let protect = memory_basic_info.Protect.0;
+        if memory_basic_info.State != MEM_COMMIT || protect & (PAGE_NOACCESS.0 | PAGE_GUARD.0) != 0 {
+            continue;
+        }
         vec.push((base_addr, size))
     }
 
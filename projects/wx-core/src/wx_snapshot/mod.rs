@@ -0,0 +1,160 @@
+use crate::WxResult;
+use chrono::Local;
+use std::{
+    ffi::OsStr,
+    fs::{File, create_dir_all, remove_file},
+    io::Read,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tracing::{debug, trace, warn};
+use walkdir::WalkDir;
+
+/// 复制正在使用的数据库, 生成可以在微信退出后解密的快照
+///
+/// 数据库和它的 `-wal`, `-shm` 文件一起复制, 复制前后重新读取文件头和 WAL 的盐, 不一致时重试
+#[derive(Debug)]
+pub struct WxSnapshot {
+    /// 账号的 `Msg` 文件夹
+    pub source_path: PathBuf,
+    /// 快照存放的文件夹, 和 `Msg` 文件夹的结构相同
+    pub output_path: PathBuf,
+    /// 复制前后状态不一致时重试的次数
+    pub retries: usize,
+    /// 每次重试前等待的时间
+    pub retry_delay: Duration,
+}
+
+/// 单个数据库的快照结果
+#[derive(Clone, Debug)]
+pub struct WxSnapshotFile {
+    /// 相对于 `Msg` 文件夹的路径
+    pub file: PathBuf,
+    /// 复制的次数
+    pub attempts: usize,
+    /// 复制前后数据库和 WAL 是否一致, 重试次数用完后仍不一致时保留最后一次复制的结果
+    pub consistent: bool,
+    /// 最后一次复制失败的原因
+    pub error: Option<String>,
+}
+
+/// 复制时需要保持一致的状态
+#[derive(Debug, PartialEq, Eq)]
+struct FileState {
+    db_size: u64,
+    db_modified: Option<SystemTime>,
+    /// 第一页, 加密数据库的盐和文件头都在这里
+    db_header: Vec<u8>,
+    wal_size: Option<u64>,
+    wal_modified: Option<SystemTime>,
+    /// WAL 的文件头, 包括检查点序号和盐
+    wal_header: Vec<u8>,
+}
+
+impl Default for WxSnapshot {
+    fn default() -> Self {
+        Self { source_path: PathBuf::new(), output_path: PathBuf::new(), retries: 5, retry_delay: Duration::from_millis(500) }
+    }
+}
+
+impl WxSnapshot {
+    /// `root` 下以当前时间命名的文件夹
+    pub fn timestamp_dir(root: &Path) -> PathBuf {
+        root.join(Local::now().format("%Y%m%d-%H%M%S").to_string())
+    }
+    /// 复制文件夹下所有的数据库, 单个文件复制失败不会中断其他文件
+    pub fn snapshot(&self) -> WxResult<Vec<WxSnapshotFile>> {
+        let mut files = vec![];
+        for entry in WalkDir::new(&self.source_path).sort_by_file_name() {
+            let entry = entry.map_err(std::io::Error::from)?;
+            if entry.path().extension() == Some(OsStr::new("db")) {
+                let relative = entry.path().strip_prefix(&self.source_path)?;
+                files.push(self.snapshot_file(relative));
+            }
+        }
+        Ok(files)
+    }
+    fn snapshot_file(&self, file: &Path) -> WxSnapshotFile {
+        let mut record = WxSnapshotFile { file: file.to_path_buf(), attempts: 0, consistent: false, error: None };
+        while record.attempts <= self.retries {
+            if record.attempts > 0 {
+                std::thread::sleep(self.retry_delay);
+            }
+            record.attempts += 1;
+            match self.copy_once(file) {
+                Ok(true) => {
+                    trace!("复制成功: {}", file.display());
+                    record.consistent = true;
+                    record.error = None;
+                    break;
+                }
+                Ok(false) => {
+                    debug!("复制时文件发生了变化, 重试: {}", file.display());
+                    record.error = None;
+                }
+                Err(e) => {
+                    debug!("复制失败, 重试: {}, {}", file.display(), e);
+                    record.error = Some(e.to_string());
+                }
+            }
+        }
+        if !record.consistent {
+            warn!("{} 次复制后仍然无法得到一致的快照: {}", record.attempts, file.display());
+        }
+        record
+    }
+    /// 复制一次, 返回复制前后的状态是否一致
+    fn copy_once(&self, file: &Path) -> WxResult<bool> {
+        let source = self.source_path.join(file);
+        let target = self.output_path.join(file);
+        if let Some(parent) = target.parent() {
+            create_dir_all(parent)?;
+        }
+        let before = FileState::read(&source)?;
+        std::fs::copy(&source, &target)?;
+        for extension in ["db-wal", "db-shm"] {
+            let (from, to) = (source.with_extension(extension), target.with_extension(extension));
+            if from.exists() {
+                std::fs::copy(&from, &to)?;
+            }
+            else if to.exists() {
+                remove_file(&to)?;
+            }
+        }
+        let after = FileState::read(&source)?;
+        let copied = FileState::read(&target)?;
+        Ok(before == after && before.same_content(&copied))
+    }
+}
+
+impl FileState {
+    fn read(file_db: &Path) -> WxResult<Self> {
+        let file_wal = file_db.with_extension("db-wal");
+        let db = std::fs::metadata(file_db)?;
+        let wal = std::fs::metadata(&file_wal).ok();
+        Ok(Self {
+            db_size: db.len(),
+            db_modified: db.modified().ok(),
+            db_header: read_head(file_db, 4096)?,
+            wal_size: wal.as_ref().map(|m| m.len()),
+            wal_modified: wal.and_then(|m| m.modified().ok()),
+            wal_header: match file_wal.exists() {
+                true => read_head(&file_wal, 32)?,
+                false => vec![],
+            },
+        })
+    }
+    /// 复制出的文件修改时间不同, 只比较长度和文件头
+    fn same_content(&self, other: &Self) -> bool {
+        self.db_size == other.db_size
+            && self.db_header == other.db_header
+            && self.wal_size == other.wal_size
+            && self.wal_header == other.wal_header
+    }
+}
+
+fn read_head(path: &Path, length: u64) -> WxResult<Vec<u8>> {
+    let mut head = vec![];
+    File::open(path)?.take(length).read_to_end(&mut head)?;
+    Ok(head)
+}
@@ -0,0 +1,115 @@
+use sqlx::{
+    Connection,
+    sqlite::{SqliteConnectOptions, SqliteConnection, SqliteJournalMode},
+};
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+/// 测试用的临时文件夹, 每次都会清空
+pub fn temp_dir(name: &str) -> PathBuf {
+    let root = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    root
+}
+
+/// 只有第一页的空数据库, 带有指定长度的保留区
+///
+/// SQLite 之后写入时会沿用文件头中的保留区长度, 和微信解密出的数据库一样
+pub fn empty_database(page_size: usize, reserve: u8) -> Vec<u8> {
+    let mut page = vec![0u8; page_size];
+    page[0..16].copy_from_slice(b"SQLite format 3\0");
+    page[16..18].copy_from_slice(&(page_size as u16).to_be_bytes());
+    page[18] = 1;
+    page[19] = 1;
+    page[20] = reserve;
+    page[21..24].copy_from_slice(&[64, 32, 32]);
+    page[24..28].copy_from_slice(&1u32.to_be_bytes());
+    page[28..32].copy_from_slice(&1u32.to_be_bytes());
+    page[44..48].copy_from_slice(&4u32.to_be_bytes());
+    page[56..60].copy_from_slice(&1u32.to_be_bytes());
+    page[92..96].copy_from_slice(&1u32.to_be_bytes());
+    page[96..100].copy_from_slice(&3045000u32.to_be_bytes());
+    // sqlite_schema 表的根节点, 一个空的叶子页
+    page[100] = 0x0D;
+    page[105..107].copy_from_slice(&((page_size - reserve as usize) as u16).to_be_bytes());
+    page
+}
+
+/// 生成 WAL 文件, 每一帧为 `(页号, 页的内容, 提交后的总页数)`, 总页数为 0 表示未提交
+///
+/// 帧中的页原样写入, 可以是明文也可以是密文, 校验和使用大端格式
+pub fn wal_file(page_size: usize, frames: &[(u32, Vec<u8>, u32)]) -> Vec<u8> {
+    let mut out = vec![];
+    out.extend(0x377f0683u32.to_be_bytes());
+    out.extend(3007000u32.to_be_bytes());
+    out.extend((page_size as u32).to_be_bytes());
+    out.extend(0u32.to_be_bytes());
+    out.extend([0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]);
+    let (mut s1, mut s2) = wal_check_sum(0, 0, &out[..24]);
+    out.extend(s1.to_be_bytes());
+    out.extend(s2.to_be_bytes());
+    for (page, data, commit) in frames {
+        let mut header = vec![];
+        header.extend(page.to_be_bytes());
+        header.extend(commit.to_be_bytes());
+        header.extend(&out[16..24].to_vec());
+        (s1, s2) = wal_check_sum(s1, s2, &header[..8]);
+        (s1, s2) = wal_check_sum(s1, s2, data);
+        header.extend(s1.to_be_bytes());
+        header.extend(s2.to_be_bytes());
+        out.extend(header);
+        out.extend(data);
+    }
+    out
+}
+
+fn wal_check_sum(mut s1: u32, mut s2: u32, data: &[u8]) -> (u32, u32) {
+    for pair in data.chunks(8) {
+        s1 = s1.wrapping_add(u32::from_be_bytes(pair[0..4].try_into().unwrap())).wrapping_add(s2);
+        s2 = s2.wrapping_add(u32::from_be_bytes(pair[4..8].try_into().unwrap())).wrapping_add(s1);
+    }
+    (s1, s2)
+}
+
+/// 写入一个带有 `MSG` 表的数据库, 使用回滚日志, 不会留下 WAL
+pub async fn sample_database(path: &Path, reserve: u8, rows: usize) {
+    std::fs::write(path, empty_database(4096, reserve)).unwrap();
+    let options = SqliteConnectOptions::new().filename(path).journal_mode(SqliteJournalMode::Delete);
+    let mut connection = SqliteConnection::connect_with(&options).await.unwrap();
+    sqlx::query("CREATE TABLE MSG (localId INTEGER PRIMARY KEY, StrTalker TEXT, StrContent TEXT)")
+        .execute(&mut connection)
+        .await
+        .unwrap();
+    connection.close().await.unwrap();
+    insert_messages(path, 0..rows).await;
+}
+
+/// 向 `MSG` 表追加消息
+pub async fn insert_messages(path: &Path, rows: Range<usize>) {
+    let options = SqliteConnectOptions::new().filename(path).journal_mode(SqliteJournalMode::Delete);
+    let mut connection = SqliteConnection::connect_with(&options).await.unwrap();
+    for i in rows {
+        sqlx::query("INSERT INTO MSG (StrTalker, StrContent) VALUES (?, ?)")
+            .bind(format!("wxid_{}", i % 7))
+            .bind(format!("第 {} 条消息 {}", i, "内容".repeat(i % 50)))
+            .execute(&mut connection)
+            .await
+            .unwrap();
+    }
+    connection.close().await.unwrap();
+}
+
+/// 读取 `MSG` 表的所有内容
+pub async fn read_messages(path: &Path) -> Vec<(i64, String, String)> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let mut connection = SqliteConnection::connect_with(&options).await.unwrap();
+    let rows = sqlx::query_as("SELECT localId, StrTalker, StrContent FROM MSG ORDER BY localId")
+        .fetch_all(&mut connection)
+        .await
+        .unwrap();
+    connection.close().await.unwrap();
+    rows
+}
@@ -1,6 +1,14 @@
+mod fixtures;
+mod on_dump;
+#[cfg(target_os = "linux")]
+mod on_linux;
+mod orm_types;
+mod wx_account;
+mod wx_decrypt;
+mod wx_encrypt;
+mod wx_snapshot;
+
 #[test]
 fn ready() {
     println!("it works!")
 }
-
-
@@ -0,0 +1,281 @@
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::path::PathBuf;
+use wx_core::{WeChatProfile, WxKeyChecker, WxMemory, WxMemoryDump, save_offset_map};
+
+const MODULE_BASE: u64 = 0x1000_0000;
+const MODULE_SIZE: u64 = 0x2000;
+const HEAP_BASE: u64 = 0x2000_0000;
+const HEAP_SIZE: u64 = 0x100;
+const KEY: [u8; 32] = *b"0123456789abcdef0123456789ABCDEF";
+const DECOY: [u8; 32] = *b"ABCDEFGHIJKLMNOPQRSTUVWXYZ012345";
+
+/// 模块中依次放置昵称, 账号, 手机号和秘钥指针, 版本号放在资源目录的 `VS_FIXEDFILEINFO` 中
+fn module_image() -> Vec<u8> {
+    let mut image = vec![0u8; MODULE_SIZE as usize];
+    image[0..2].copy_from_slice(b"MZ");
+    image[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+    image[0x40..0x44].copy_from_slice(b"PE\0\0");
+    image[0x54..0x56].copy_from_slice(&136u16.to_le_bytes());
+    image[0x58..0x5A].copy_from_slice(&0x20Bu16.to_le_bytes());
+    image[0xD8..0xDC].copy_from_slice(&0x800u32.to_le_bytes());
+    image[0xDC..0xE0].copy_from_slice(&0x100u32.to_le_bytes());
+    image[0x100..0x100 + 7].copy_from_slice("张三\0".as_bytes());
+    image[0x200..0x200 + 10].copy_from_slice(b"wxid_test\0");
+    image[0x300..0x300 + 12].copy_from_slice(b"13800000000\0");
+    image[0x400..0x408].copy_from_slice(&(HEAP_BASE + 0x10).to_le_bytes());
+    image[0x800..0x804].copy_from_slice(&0xFEEF04BDu32.to_le_bytes());
+    image[0x808..0x80C].copy_from_slice(&(9u32 << 16 | 9).to_le_bytes());
+    image[0x80C..0x810].copy_from_slice(&(9u32 << 16 | 9).to_le_bytes());
+    image
+}
+
+/// 堆中放置秘钥和一个假秘钥, 都以 `(指针, 长度 32)` 的形式引用, 另有一个指向地址末尾的无效指针
+fn heap_image() -> Vec<u8> {
+    let mut image = vec![0u8; HEAP_SIZE as usize];
+    image[0x10..0x30].copy_from_slice(&KEY);
+    image[0x30..0x38].copy_from_slice(&(HEAP_BASE + 0x60).to_le_bytes());
+    image[0x38..0x40].copy_from_slice(&32u64.to_le_bytes());
+    image[0x40..0x48].copy_from_slice(&(HEAP_BASE + 0x10).to_le_bytes());
+    image[0x48..0x50].copy_from_slice(&32u64.to_le_bytes());
+    image[0x60..0x80].copy_from_slice(&DECOY);
+    image[0x80..0x88].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+    image[0x88..0x90].copy_from_slice(&32u64.to_le_bytes());
+    image
+}
+
+/// 只有第一页的加密数据库, 只保证 hmac 正确
+fn encrypted_database(key: &[u8]) -> Vec<u8> {
+    let mut page: Vec<u8> = (0..4096u32).map(|i| (i * 7 + 3) as u8).collect();
+    let salt = page[0..16].to_vec();
+    let mut byte_key = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha1>>(key, &salt, 64000, &mut byte_key).unwrap();
+    let mac_salt: Vec<u8> = salt.iter().map(|i| i ^ 58).collect();
+    let mut mac_key = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha1>>(&byte_key, &mac_salt, 2, &mut mac_key).unwrap();
+    let mut mac = Hmac::<Sha1>::new_from_slice(&mac_key).unwrap();
+    mac.update(&page[16..4096 - 32]);
+    mac.update(&1u32.to_le_bytes());
+    page[4096 - 32..4096 - 12].copy_from_slice(&mac.finalize().into_bytes());
+    page
+}
+
+fn temp_file(name: &str, data: &[u8]) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+fn offset_map() -> Option<String> {
+    let path = temp_file("on_dump_offsets.json", br#"{"9.9.9.9": [256, 512, 768, 0, 1024]}"#);
+    Some(path.to_string_lossy().to_string())
+}
+
+fn build_minidump() -> Vec<u8> {
+    let name: Vec<u8> = "C:\\Program Files\\Tencent\\WeChat\\WeChatWin.dll".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let module_list = 68u32;
+    let name_rva = module_list + 4 + 108;
+    let memory_list = name_rva + 4 + name.len() as u32;
+    let base_rva = memory_list as u64 + 16 + 2 * 16;
+    let mut out = vec![];
+    out.extend(b"MDMP");
+    out.extend(0xA793u32.to_le_bytes());
+    out.extend(2u32.to_le_bytes());
+    out.extend(32u32.to_le_bytes());
+    out.extend([0u8; 16]);
+    // directory
+    for (kind, size, rva) in [(4u32, 4 + 108u32, module_list), (9, 16 + 2 * 16, memory_list)] {
+        out.extend(kind.to_le_bytes());
+        out.extend(size.to_le_bytes());
+        out.extend(rva.to_le_bytes());
+    }
+    out.extend([0u8; 12]);
+    assert_eq!(out.len(), module_list as usize);
+    out.extend(1u32.to_le_bytes());
+    let mut module = vec![0u8; 108];
+    module[0..8].copy_from_slice(&MODULE_BASE.to_le_bytes());
+    module[8..12].copy_from_slice(&(MODULE_SIZE as u32).to_le_bytes());
+    module[20..24].copy_from_slice(&name_rva.to_le_bytes());
+    out.extend(module);
+    out.extend((name.len() as u32).to_le_bytes());
+    out.extend(&name);
+    out.extend(2u64.to_le_bytes());
+    out.extend(base_rva.to_le_bytes());
+    for (start, size) in [(MODULE_BASE, MODULE_SIZE), (HEAP_BASE, HEAP_SIZE)] {
+        out.extend(start.to_le_bytes());
+        out.extend(size.to_le_bytes());
+    }
+    assert_eq!(out.len(), base_rva as usize);
+    out.extend(module_image());
+    out.extend(heap_image());
+    out
+}
+
+fn build_elf_core() -> Vec<u8> {
+    let path = b"/home/user/.wine/drive_c/Program Files/Tencent/WeChat/WeChatWin.dll\0";
+    let mut desc = vec![];
+    desc.extend(1u64.to_le_bytes());
+    desc.extend(4096u64.to_le_bytes());
+    desc.extend(MODULE_BASE.to_le_bytes());
+    desc.extend((MODULE_BASE + MODULE_SIZE).to_le_bytes());
+    desc.extend(0u64.to_le_bytes());
+    desc.extend(path);
+    while desc.len() % 4 != 0 {
+        desc.push(0);
+    }
+    let mut note = vec![];
+    note.extend(5u32.to_le_bytes());
+    note.extend((desc.len() as u32).to_le_bytes());
+    note.extend(0x46494c45u32.to_le_bytes());
+    note.extend(b"CORE\0\0\0\0");
+    note.extend(desc);
+    let phoff = 64u64;
+    let note_offset = phoff + 3 * 56;
+    let module_offset = note_offset + note.len() as u64;
+    let heap_offset = module_offset + MODULE_SIZE;
+    let mut out = vec![0u8; 64];
+    out[0..4].copy_from_slice(b"\x7FELF");
+    out[4] = 2;
+    out[5] = 1;
+    out[6] = 1;
+    out[16..18].copy_from_slice(&4u16.to_le_bytes());
+    out[18..20].copy_from_slice(&62u16.to_le_bytes());
+    out[32..40].copy_from_slice(&phoff.to_le_bytes());
+    out[52..54].copy_from_slice(&64u16.to_le_bytes());
+    out[54..56].copy_from_slice(&56u16.to_le_bytes());
+    out[56..58].copy_from_slice(&3u16.to_le_bytes());
+    for (kind, offset, vaddr, size) in [
+        (4u32, note_offset, 0, note.len() as u64),
+        (1, module_offset, MODULE_BASE, MODULE_SIZE),
+        (1, heap_offset, HEAP_BASE, HEAP_SIZE),
+    ] {
+        let mut header = vec![0u8; 56];
+        header[0..4].copy_from_slice(&kind.to_le_bytes());
+        header[8..16].copy_from_slice(&offset.to_le_bytes());
+        header[16..24].copy_from_slice(&vaddr.to_le_bytes());
+        header[32..40].copy_from_slice(&size.to_le_bytes());
+        header[40..48].copy_from_slice(&size.to_le_bytes());
+        out.extend(header);
+    }
+    out.extend(note);
+    // 模拟没有转储 PE 头的情况, 版本号只能在模块范围内查找
+    let mut module = module_image();
+    module[..0x40].fill(0);
+    out.extend(module);
+    out.extend(heap_image());
+    out
+}
+
+fn check_dump(dump: &WxMemoryDump) {
+    let profile = dump.profile();
+    assert_eq!(profile.version, "9.9.9.9");
+    assert_eq!(profile.nick_name, "张三");
+    assert_eq!(profile.user_name, "wxid_test");
+    assert_eq!(profile.mobile, "13800000000");
+    assert_eq!(profile.aes256, KEY);
+    assert_eq!(dump.read_memory(0x200, 9, false).unwrap(), b"wxid_test");
+    assert_eq!(dump.read_memory(HEAP_BASE as usize + 0x10, 32, true).unwrap(), KEY);
+    assert_eq!(dump.memory_search(b"wxid_test", false).unwrap(), vec![0x200]);
+    assert_eq!(dump.memory_search(b"wxid_test", true).unwrap(), vec![MODULE_BASE as usize + 0x200]);
+    assert_eq!(dump.search_in_all_regions(&KEY).unwrap(), vec![HEAP_BASE as usize + 0x10]);
+    assert!(dump.read_memory(0x3000_0000, 4, true).is_err());
+}
+
+#[test]
+fn open_minidump() {
+    let path = temp_file("wechat.dmp", &build_minidump());
+    let mut dump = WxMemoryDump::default();
+    dump.open_wechat_dump(&path, &offset_map(), "WeChatWin.dll").unwrap();
+    check_dump(&dump);
+}
+
+#[test]
+fn open_elf_core() {
+    let path = temp_file("wechat.core", &build_elf_core());
+    let mut dump = WxMemoryDump::default();
+    dump.open_wechat_dump(&path, &offset_map(), "WeChatWin.dll").unwrap();
+    check_dump(&dump);
+    assert_eq!(dump.modules(), vec![("WeChatWin.dll", MODULE_BASE as usize, MODULE_SIZE as usize)]);
+}
+
+#[test]
+fn open_unknown_dump() {
+    let path = temp_file("wechat.txt", b"not a dump file");
+    let mut dump = WxMemoryDump::default();
+    assert!(dump.open_wechat_dump_with_out_info(&path, "WeChatWin.dll").is_err());
+}
+
+#[test]
+fn open_corrupt_minidump() {
+    let name_rva = 68 + 4 + 108;
+    let name_length = u32::from_le_bytes(build_minidump()[name_rva..name_rva + 4].try_into().unwrap()) as usize;
+    let memory_list = name_rva + 4 + name_length;
+    let mut streams = build_minidump();
+    streams[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut name = build_minidump();
+    name[name_rva..name_rva + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut overflow = build_minidump();
+    overflow[memory_list + 32..memory_list + 40].copy_from_slice(&(usize::MAX as u64).to_le_bytes());
+    let mut truncated = build_minidump();
+    truncated.truncate(truncated.len() - 16);
+    for (index, data) in [streams, name, overflow, truncated].iter().enumerate() {
+        let path = temp_file(&format!("wechat_corrupt_{}.dmp", index), data);
+        let mut dump = WxMemoryDump::default();
+        assert!(dump.open_wechat_dump_with_out_info(&path, "WeChatWin.dll").is_err(), "case {}", index);
+    }
+}
+
+#[test]
+fn find_key_in_dump() {
+    let path = temp_file("wechat_find_key.dmp", &build_minidump());
+    let mut dump = WxMemoryDump::default();
+    dump.open_wechat_dump_with_out_info(&path, "WeChatWin.dll").unwrap();
+    let checker = WxKeyChecker::new(&temp_file("MicroMsg.db", &encrypted_database(&KEY))).unwrap();
+    let found = dump.find_key(&checker).unwrap().unwrap();
+    assert_eq!(found.aes256, KEY);
+    assert_eq!(found.address, HEAP_BASE as usize + 0x10);
+    assert_eq!(found.pointer, HEAP_BASE as usize + 0x40);
+    let checker = WxKeyChecker::new(&temp_file("Other.db", &encrypted_database(&[7; 32]))).unwrap();
+    assert!(dump.find_key(&checker).unwrap().is_none());
+}
+
+#[test]
+fn check_plain_database() {
+    let mut page = vec![0u8; 4096];
+    page[0..16].copy_from_slice(b"SQLite format 3\0");
+    assert!(WxKeyChecker::new(&temp_file("Plain.db", &page)).is_err());
+}
+
+#[test]
+fn discover_offsets_in_dump() {
+    let path = temp_file("wechat_discover.dmp", &build_minidump());
+    let mut dump = WxMemoryDump::default();
+    dump.open_wechat_dump_with_out_info(&path, "WeChatWin.dll").unwrap();
+    assert_eq!(dump.version().unwrap(), "9.9.9.9");
+    let known = WeChatProfile {
+        nick_name: "张三".to_string(),
+        user_name: "wxid_test".to_string(),
+        mobile: "13800000000".to_string(),
+        ..Default::default()
+    };
+    let checker = WxKeyChecker::new(&temp_file("MicroMsg_discover.db", &encrypted_database(&KEY))).unwrap();
+    let offsets = dump.discover_offsets(&known, &checker).unwrap();
+    assert_eq!(offsets, vec![0x100, 0x200, 0x300, 0, 0x400]);
+    let map = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("discovered_offsets.json");
+    let _ = std::fs::remove_file(&map);
+    save_offset_map(&map, "9.9.9.9", &offsets).unwrap();
+    let text = std::fs::read_to_string(&map).unwrap();
+    assert!(text.starts_with("{\n    \"3.2.1.15\": [\n        328121948,"));
+    assert!(
+        text.trim_end()
+            .ends_with("\"9.9.9.9\": [\n        256,\n        512,\n        768,\n        0,\n        1024\n    ]\n}")
+    );
+    // 替换已有的版本时保持原来的位置
+    save_offset_map(&map, "3.2.1.15", &[1, 2, 3, 4, 5]).unwrap();
+    let text = std::fs::read_to_string(&map).unwrap();
+    assert!(text.starts_with("{\n    \"3.2.1.15\": [\n        1,"));
+    assert!(text.trim_end().ends_with("1024\n    ]\n}"));
+    let mut dump = WxMemoryDump::default();
+    dump.open_wechat_dump(&path, &Some(map.to_string_lossy().to_string()), "WeChatWin.dll").unwrap();
+    check_dump(&dump);
+}
@@ -0,0 +1,85 @@
+use std::{
+    env::current_exe,
+    io::{BufRead, BufReader, Lines, Read},
+    process::{Child, ChildStdout, Command, Stdio},
+};
+use wx_core::{WxMemory, WxScanner};
+
+static PLANTED_STATIC: [u8; 24] = *b"wx-dump planted static!\x01";
+const PLANTED_HEAP: &[u8] = b"wx-dump planted on heap\x02";
+
+/// 被扫描的子进程, 在内存中放置已知的数据后等待父进程关闭 stdin
+///
+/// 只由 [`Helper::spawn`] 以 `--ignored` 启动
+#[test]
+#[ignore]
+fn scanner_helper() {
+    let heap = std::hint::black_box(PLANTED_HEAP.to_vec());
+    println!("PLANTED {} {}", std::hint::black_box(&PLANTED_STATIC).as_ptr() as usize, heap.as_ptr() as usize);
+    let _ = std::io::stdin().read(&mut [0u8; 1]);
+    drop(heap);
+}
+
+struct Helper {
+    child: Child,
+    /// 保持管道打开, 避免子进程写入时出错
+    _stdout: Lines<BufReader<ChildStdout>>,
+    static_addr: usize,
+    heap_addr: usize,
+}
+
+impl Helper {
+    fn spawn() -> Helper {
+        let mut child = Command::new(current_exe().unwrap())
+            .args(["on_linux::scanner_helper", "--exact", "--ignored", "--nocapture", "--test-threads=1"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        // libtest 会在同一行先输出测试名
+        let line = lines.find_map(|line| Some(line.ok()?.split_once("PLANTED ")?.1.to_string())).unwrap();
+        let mut parts = line.split(' ').map(|s| s.parse::<usize>().unwrap());
+        Helper { static_addr: parts.next().unwrap(), heap_addr: parts.next().unwrap(), child, _stdout: lines }
+    }
+    fn open(&self) -> WxScanner {
+        let module_name = current_exe().unwrap().file_name().unwrap().to_str().unwrap().to_string();
+        let mut scanner = WxScanner::default();
+        scanner.open_wechat_process_with_out_info(&Some(self.child.id()), "", &module_name).unwrap();
+        scanner
+    }
+}
+
+impl Drop for Helper {
+    fn drop(&mut self) {
+        drop(self.child.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn read_planted_memory() {
+    let helper = Helper::spawn();
+    let scanner = helper.open();
+    assert_eq!(scanner.read_memory(helper.heap_addr, PLANTED_HEAP.len(), true).unwrap(), PLANTED_HEAP);
+    assert_eq!(scanner.read_memory(helper.static_addr, PLANTED_STATIC.len(), true).unwrap(), PLANTED_STATIC);
+}
+
+#[test]
+fn search_planted_memory() {
+    let helper = Helper::spawn();
+    let scanner = helper.open();
+    let absolute = scanner.memory_search(&PLANTED_STATIC, true).unwrap();
+    assert!(absolute.contains(&helper.static_addr));
+    let relative = scanner.memory_search(&PLANTED_STATIC, false).unwrap();
+    assert_eq!(relative.len(), absolute.len());
+    for offset in relative {
+        assert_eq!(scanner.read_memory(offset, PLANTED_STATIC.len(), false).unwrap(), PLANTED_STATIC);
+    }
+}
+
+#[test]
+fn open_missing_process() {
+    let mut scanner = WxScanner::default();
+    assert!(scanner.open_wechat_process_with_out_info(&None, "wx-dump-no-such-process", "WeChatWin.dll").is_err());
+}
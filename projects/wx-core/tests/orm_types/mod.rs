@@ -0,0 +1,452 @@
+use crate::fixtures::temp_dir;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use sqlx::{Connection, SqliteConnection, sqlite::SqliteConnectOptions};
+use std::path::Path;
+use wx_core::{
+    AppMsg, BytesExtra, ChatRoomMember, ContactKind, ForwardRecord, Message, ProtoReader, ReferMsg, WireValue, WxExport,
+};
+
+/// 群聊中的图片消息, 依次为发送者, 消息来源, 缩略图和原图
+const GROUP_IMAGE_EXTRA: &str = concat!(
+    "CgQIEBAAGhEIARINd3hpZF9zZW5kZXIwMRp8CAcSeDxtc2dzb3VyY2U+PHNlY19tc2dfbm9kZT48dXVpZD40ZTZjMWY8L3V1aWQ+PC9zZWNfbXNnX25vZGU+",
+    "PHNpbGVuY2U+MTwvc2lsZW5jZT48bWVtYmVyY291bnQ+MjU8L21lbWJlcmNvdW50PjwvbXNnc291cmNlPhpdCAMSWXd4aWRfbWVcRmlsZVN0b3JhZ2VcTXNn",
+    "QXR0YWNoXDllMjBmNDc4ODk5ZGMyOWViMTk3NDEzODZmOTM0M2M4XFRodW1iXDIwMjMtMTBcYTFiMmMzX3QuZGF0GlsIBBJXd3hpZF9tZVxGaWxlU3RvcmFn",
+    "ZVxNc2dBdHRhY2hcOWUyMGY0Nzg4OTlkYzI5ZWIxOTc0MTM4NmY5MzQzYzhcSW1hZ2VcMjAyMy0xMFxhMWIyYzMuZGF0",
+);
+/// 私聊中自己发送的文件, 没有发送者
+const PRIVATE_FILE_EXTRA: &str = "CgQIEBAAGjgIBxI0PG1zZ3NvdXJjZT48c2lnbmF0dXJlPnYxX2FiYzwvc2lnbmF0dXJlPjwvbXNnc291cmNlPhovCAQSK3d4aWRfbWVcRmlsZVN0b3JhZ2VcRmlsZVwyMDIzLTEwXOaKpeWRii5wZGY=";
+
+fn extra(blob: &str) -> BytesExtra {
+    BytesExtra::parse(&BASE64_STANDARD.decode(blob).unwrap()).unwrap()
+}
+
+fn appmsg(xml: &str) -> Vec<u8> {
+    let mut data = xml.as_bytes().to_vec();
+    data.push(0);
+    lz4_flex::compress(&data)
+}
+
+#[test]
+fn parse_messages() {
+    let image = Message::parse(3, 0, r#"<msg><img md5="abc" length="10"/></msg>"#, &[], &extra(GROUP_IMAGE_EXTRA));
+    assert_eq!(
+        image,
+        Message::Image {
+            md5: Some("abc".to_string()),
+            path: Some(
+                "wxid_me\\FileStorage\\MsgAttach\\9e20f478899dc29eb19741386f9343c8\\Image\\2023-10\\a1b2c3.dat".to_string()
+            )
+        }
+    );
+    let voice = Message::parse(34, 0, r#"<msg><voicemsg endflag="1" voicelength="2500" /></msg>"#, &[], &BytesExtra::default());
+    assert_eq!(voice, Message::Voice { duration_ms: 2500 });
+    assert_eq!(voice.to_string(), "[语音] 3 秒");
+    let emoji = Message::parse(
+        47,
+        0,
+        r#"<msg><emoji fromusername="a" md5="e1" cdnurl="http://x?a=1&amp;b=2"/></msg>"#,
+        &[],
+        &BytesExtra::default(),
+    );
+    assert_eq!(emoji, Message::Emoji { md5: "e1".to_string(), url: Some("http://x?a=1&b=2".to_string()) });
+    let file = Message::parse(
+        49,
+        6,
+        "",
+        &appmsg(
+            "<msg><appmsg><title>报告.pdf</title><type>6</type><appattach><totallen>1024</totallen></appattach></appmsg></msg>",
+        ),
+        &BytesExtra::default(),
+    );
+    assert_eq!(file, Message::File { name: "报告.pdf".to_string(), size: 1024, path: None });
+    let reply = Message::parse(
+        49,
+        57,
+        "",
+        &appmsg(concat!(
+            "<msg><appmsg><title>好的</title><type>57</type><refermsg><type>1</type><svrid>42</svrid>",
+            "<fromusr>wxid_a</fromusr><displayname>张三</displayname><content>明天&lt;见&gt;</content></refermsg></appmsg></msg>"
+        )),
+        &BytesExtra::default(),
+    );
+    assert_eq!(
+        reply,
+        Message::Reply {
+            text: "好的".to_string(),
+            quoted: ReferMsg {
+                svrid: 42,
+                kind: 1,
+                sender: "wxid_a".to_string(),
+                display_name: "张三".to_string(),
+                content: "明天<见>".to_string()
+            }
+        }
+    );
+    assert_eq!(reply.to_string(), "回复 张三「明天<见>」: 好的");
+    let transfer = Message::parse(
+        49,
+        2000,
+        "",
+        &appmsg(
+            "<msg><appmsg><wcpayinfo><paysubtype>1</paysubtype><feedesc><![CDATA[￥0.01]]></feedesc></wcpayinfo></appmsg></msg>",
+        ),
+        &BytesExtra::default(),
+    );
+    assert_eq!(transfer, Message::Transfer { amount: "￥0.01".to_string(), memo: String::new(), pay_subtype: 1 });
+    let voip = Message::parse(
+        50,
+        0,
+        "<voipmsg><VoIPBubbleMsg><msg><![CDATA[通话时长 00:12]]></msg></VoIPBubbleMsg></voipmsg>",
+        &[],
+        &BytesExtra::default(),
+    );
+    assert_eq!(voip, Message::VoIP { text: "通话时长 00:12".to_string() });
+    let revoke = Message::parse(
+        10002,
+        0,
+        r#"<sysmsg type="revokemsg"><revokemsg><replacemsg><![CDATA["张三" 撤回了一条消息]]></replacemsg></revokemsg></sysmsg>"#,
+        &[],
+        &BytesExtra::default(),
+    );
+    assert_eq!(revoke, Message::Revoke { text: "\"张三\" 撤回了一条消息".to_string() });
+    let pat = Message::parse(
+        10000,
+        4,
+        r#"<sysmsg type="pat"><pat><template><![CDATA["张三" 拍了拍我]]></template></pat></sysmsg>"#,
+        &[],
+        &BytesExtra::default(),
+    );
+    assert_eq!(pat, Message::Pat { text: "\"张三\" 拍了拍我".to_string() });
+    let unknown = Message::parse(99, 1, "???", &[], &BytesExtra::default());
+    assert_eq!(unknown, Message::Unknown { type_id: 99, sub_id: 1, content: "???".to_string() });
+}
+
+#[test]
+fn parse_appmsg() {
+    let link = AppMsg::parse(concat!(
+        "<msg><appmsg appid=\"\"><title>标题</title><des>描述</des><type>5</type>",
+        "<url>https://mp.weixin.qq.com/s?a=1&amp;b=2</url><sourcedisplayname>公众号</sourcedisplayname></appmsg></msg>"
+    ));
+    assert_eq!((link.kind, link.title.as_str(), link.description.as_str()), (5, "标题", "描述"));
+    assert_eq!((link.url.as_str(), link.source_name.as_str()), ("https://mp.weixin.qq.com/s?a=1&b=2", "公众号"));
+    let file = AppMsg::parse(
+        "<msg><appmsg><title>a.zip</title><type>6</type><appattach><totallen>10</totallen><fileext>zip</fileext></appattach></appmsg></msg>",
+    );
+    assert_eq!(file.attachment.map(|a| (a.size, a.extension)), Some((10, "zip".to_string())));
+    let weapp = Message::parse(
+        49,
+        33,
+        concat!(
+            "<msg><appmsg><title>点外卖</title><type>33</type><sourcedisplayname>外卖</sourcedisplayname>",
+            "<weappinfo><username>gh_1@app</username><pagepath><![CDATA[pages/index.html]]></pagepath></weappinfo></appmsg></msg>"
+        ),
+        &[],
+        &BytesExtra::default(),
+    );
+    assert_eq!(
+        weapp,
+        Message::MiniProgram {
+            app_name: "外卖".to_string(),
+            title: "点外卖".to_string(),
+            page: "pages/index.html".to_string()
+        }
+    );
+    let music = Message::parse(
+        49,
+        3,
+        "<msg><appmsg><title>晴天</title><des>周杰伦</des><type>3</type><url>https://y.qq.com/1</url></appmsg></msg>",
+        &[],
+        &BytesExtra::default(),
+    );
+    assert_eq!(music.to_string(), "[音乐] 晴天 - 周杰伦");
+    let channel = Message::parse(
+        49,
+        51,
+        concat!(
+            "<msg><appmsg><title>当前版本不支持展示该内容</title><type>51</type><finderFeed><objectId>1</objectId>",
+            "<nickname>旅行</nickname><desc>看海</desc></finderFeed></appmsg></msg>"
+        ),
+        &[],
+        &BytesExtra::default(),
+    );
+    assert_eq!(channel, Message::Channel { nickname: "旅行".to_string(), description: "看海".to_string() });
+    // 引用的文件只显示标题, 过长的文本会被截断
+    let quoted_file = ReferMsg {
+        kind: 49,
+        display_name: "李四".to_string(),
+        content: "<msg><appmsg><title>报告.pdf</title><type>6</type></appmsg></msg>".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(quoted_file.summary(), "报告.pdf");
+    let long = Message::Reply {
+        text: "收到".to_string(),
+        quoted: ReferMsg { kind: 1, display_name: "王五".to_string(), content: "一".repeat(30), ..Default::default() },
+    };
+    assert_eq!(long.to_string(), format!("回复 王五「{}…」: 收到", "一".repeat(20)));
+}
+
+#[test]
+fn parse_forward_records() {
+    let record = concat!(
+        "<recordinfo><title>群聊的聊天记录</title><datalist count=\"3\">",
+        "<dataitem datatype=\"1\" dataid=\"a\"><datadesc>早上好</datadesc><sourcename>张三</sourcename>",
+        "<sourcetime>2023-10-1 09:00</sourcetime></dataitem>",
+        "<dataitem datatype=\"17\" dataid=\"b\"><datatitle>李四和王五的聊天记录</datatitle><datadesc>李四: 你好</datadesc>",
+        "<recordxml><recordinfo><datalist count=\"1\"><dataitem datatype=\"2\"><sourcename>王五</sourcename>",
+        "<sourcetime>2023-9-30 20:00</sourcetime></dataitem></datalist></recordinfo></recordxml>",
+        "<sourcename>李四</sourcename><sourcetime>2023-10-1 09:01</sourcetime></dataitem>",
+        "<dataitem datatype=\"8\"><datatitle>报告.pdf</datatitle><sourcename>张三</sourcename>",
+        "<sourcetime>2023-10-1 09:02</sourcetime></dataitem>",
+        "</datalist></recordinfo>",
+    );
+    let records = ForwardRecord::parse_list(record);
+    assert_eq!(records.len(), 3);
+    assert_eq!((records[1].datatype, records[1].sender.as_str()), (17, "李四"));
+    assert_eq!(
+        records[1].children,
+        [ForwardRecord {
+            datatype: 2, sender: "王五".to_string(), time: "2023-9-30 20:00".to_string(), ..Default::default()
+        }]
+    );
+    let message = Message::parse(
+        49,
+        19,
+        "",
+        &appmsg(&format!(
+            "<msg><appmsg><title>群聊的聊天记录</title><type>19</type><recorditem><![CDATA[{}]]></recorditem></appmsg></msg>",
+            record
+        )),
+        &BytesExtra::default(),
+    );
+    assert_eq!(
+        message.to_string(),
+        concat!(
+            "[聊天记录] 群聊的聊天记录\n",
+            "  张三 2023-10-1 09:00: 早上好\n",
+            "  李四 2023-10-1 09:01: [聊天记录] 李四和王五的聊天记录\n",
+            "    王五 2023-9-30 20:00: [图片]\n",
+            "  张三 2023-10-1 09:02: [文件] 报告.pdf"
+        )
+    );
+}
+
+#[test]
+fn decode_bytes_extra() {
+    let group = extra(GROUP_IMAGE_EXTRA);
+    assert_eq!(group.sender(), Some("wxid_sender01"));
+    assert_eq!(group.entries.iter().map(|(k, _)| *k).collect::<Vec<_>>(), [1, 7, 3, 4]);
+    assert_eq!(
+        group.resource_paths(),
+        [
+            "wxid_me\\FileStorage\\MsgAttach\\9e20f478899dc29eb19741386f9343c8\\Thumb\\2023-10\\a1b2c3_t.dat",
+            "wxid_me\\FileStorage\\MsgAttach\\9e20f478899dc29eb19741386f9343c8\\Image\\2023-10\\a1b2c3.dat",
+        ]
+    );
+    let private = extra(PRIVATE_FILE_EXTRA);
+    assert_eq!(private.sender(), None);
+    assert_eq!(private.resource(), Some("wxid_me\\FileStorage\\File\\2023-10\\报告.pdf"));
+    assert_eq!(BytesExtra::parse(&[]).unwrap(), BytesExtra::default());
+    // 长度超出剩余数据的字段
+    assert!(BytesExtra::parse(&[0x1A, 0x10, 0x08]).is_err());
+}
+
+#[test]
+fn decode_wire_format() {
+    // 字段 1 为 150, 字段 2 为 "testing", 字段 3 为 fixed32, 字段 4 为 fixed64
+    let data = [
+        0x08, 0x96, 0x01, 0x12, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g', 0x1D, 1, 0, 0, 0, 0x21, 2, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    let fields = ProtoReader::fields(&data).unwrap();
+    let values: Vec<_> = fields.iter().map(|f| (f.number, f.value)).collect();
+    assert_eq!(
+        values,
+        [
+            (1, WireValue::Varint(150)),
+            (2, WireValue::Bytes(b"testing")),
+            (3, WireValue::Fixed32(1)),
+            (4, WireValue::Fixed64(2))
+        ]
+    );
+    assert_eq!(fields[1].value.as_str(), Some("testing"));
+    // 不完整的 varint 和不支持的 wire type
+    assert!(ProtoReader::fields(&[0x08, 0x96]).is_err());
+    assert!(ProtoReader::fields(&[0x0B]).is_err());
+    let mut reader = ProtoReader::new(&[0x0B, 0x08, 0x01]);
+    assert!(reader.next().unwrap().is_err());
+    assert!(reader.next().is_none());
+}
+
+async fn execute(path: &Path, sql: &str) {
+    let options = SqliteConnectOptions::new().filename(path).create_if_missing(true);
+    let mut connection = SqliteConnection::connect_with(&options).await.unwrap();
+    sqlx::raw_sql(sql).execute(&mut connection).await.unwrap();
+    connection.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn export_every_message() {
+    let root = temp_dir("export_messages");
+    std::fs::create_dir_all(root.join("Multi")).unwrap();
+    execute(
+        &root.join("MicroMsg.db"),
+        "CREATE TABLE Session (strUsrName TEXT, strNickName TEXT); INSERT INTO Session VALUES ('wxid_a', '张三');",
+    )
+    .await;
+    execute(
+        &root.join("Multi/MSG0.db"),
+        concat!(
+            "CREATE TABLE MSG (localId INTEGER PRIMARY KEY, Type INT, SubType INT, IsSender INT, CreateTime INT, Sequence INT, ",
+            "StrTalker TEXT, StrContent TEXT, CompressContent BLOB, BytesExtra BLOB);",
+            "INSERT INTO MSG VALUES (1, 1, 0, 1, 1700000000, 1, 'wxid_a', '你好', NULL, NULL);",
+            "INSERT INTO MSG VALUES (2, 3, 0, 0, 1700000001, 2, 'wxid_a', '<msg><img md5=\"abc\"/></msg>', NULL, NULL);",
+            "INSERT INTO MSG VALUES (3, 43, 0, 0, 1700000002, 3, 'wxid_b', '<msg><videomsg playlength=\"7\"/></msg>', NULL, NULL);",
+            "INSERT INTO MSG VALUES (4, 10000, 0, 0, 1700000003, 4, 'wxid_b', '你撤回了一条消息', NULL, NULL);",
+        ),
+    )
+    .await;
+    let export = WxExport { db: root.clone(), ..Default::default() };
+    export.export_message().await.unwrap();
+    let csv = std::fs::read_to_string(root.join("MSG.csv")).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[1].contains("wxid_b") && lines[1].contains("Revoke"));
+    assert!(lines[2].contains("[视频] 7 秒") && lines[2].contains("Video"));
+    assert!(lines[3].contains("张三") && lines[3].contains("[图片]") && lines[3].contains("Image"));
+    assert!(lines[4].contains("你好") && lines[4].contains("Text") && lines[4].contains("发送"));
+}
+
+#[tokio::test]
+async fn resolve_group_senders() {
+    let root = temp_dir("export_group_senders");
+    std::fs::create_dir_all(root.join("Multi")).unwrap();
+    execute(
+        &root.join("MicroMsg.db"),
+        concat!(
+            "CREATE TABLE Session (strUsrName TEXT, strNickName TEXT);",
+            "INSERT INTO Session VALUES ('123@chatroom', '群聊');",
+            "CREATE TABLE Contact (UserName TEXT, Alias TEXT, Remark TEXT, NickName TEXT);",
+            "INSERT INTO Contact VALUES ('wxid_b', '', '', '昵称B'), ('wxid_c', '', '备注C', '昵称C'), ('wxid_d', '', NULL, '昵称D');",
+            "CREATE TABLE ChatRoom (ChatRoomName TEXT, RoomData BLOB);",
+            // wxid_b 的群昵称为 小B, wxid_c 没有设置群昵称
+            "INSERT INTO ChatRoom VALUES ('123@chatroom', X'0A100A06777869645F621204E5B08F4218000A0A0A06777869645F6318001000');",
+        ),
+    )
+    .await;
+    execute(
+        &root.join("Multi/MSG0.db"),
+        concat!(
+            "CREATE TABLE MSG (localId INTEGER PRIMARY KEY, Type INT, SubType INT, IsSender INT, CreateTime INT, Sequence INT, ",
+            "StrTalker TEXT, StrContent TEXT, CompressContent BLOB, BytesExtra BLOB);",
+            // 新版本的发送者在 BytesExtra 中
+            "INSERT INTO MSG VALUES (1, 1, 0, 0, 1700000000, 1, '123@chatroom', '第一条', NULL, X'0A04081010001A0A08011206777869645F62');",
+            // 旧版本的发送者在 StrContent 的开头
+            "INSERT INTO MSG VALUES (2, 1, 0, 0, 1700000001, 2, '123@chatroom', 'wxid_c:\n第二条', NULL, NULL);",
+            "INSERT INTO MSG VALUES (3, 1, 0, 0, 1700000002, 3, '123@chatroom', 'wxid_d:\n第三条', NULL, NULL);",
+            "INSERT INTO MSG VALUES (4, 1, 0, 0, 1700000003, 4, '123@chatroom', 'wxid_e:\n第四条', NULL, NULL);",
+            "INSERT INTO MSG VALUES (5, 1, 0, 1, 1700000004, 5, '123@chatroom', '第五条', NULL, NULL);",
+            // 系统消息没有发送者, 也不是自己发送的
+            "INSERT INTO MSG VALUES (6, 10000, 0, 0, 1700000005, 6, '123@chatroom', '小B邀请小C加入了群聊', NULL, NULL);",
+        ),
+    )
+    .await;
+    let export = WxExport { db: root.clone(), ..Default::default() };
+    export.export_message().await.unwrap();
+    let csv = std::fs::read_to_string(root.join("MSG.csv")).unwrap();
+    let lines: Vec<&str> = csv.lines().skip(1).collect();
+    let expected = [
+        ",群聊,系统,小B邀请小C加入了群聊,",
+        ",群聊,我,第五条,",
+        ",群聊,wxid_e,第四条,",
+        ",群聊,昵称D,第三条,",
+        ",群聊,备注C,第二条,",
+        ",群聊,小B,第一条,",
+    ];
+    assert_eq!(lines.len(), expected.len());
+    for (line, expected) in lines.iter().zip(expected) {
+        assert!(line.contains(expected), "{} 中没有 {}", line, expected);
+    }
+}
+
+#[tokio::test]
+async fn export_contacts() {
+    let root = temp_dir("export_contacts");
+    execute(
+        &root.join("MicroMsg.db"),
+        concat!(
+            "CREATE TABLE Contact (UserName TEXT, Alias TEXT, Remark TEXT, NickName TEXT, LabelIDList TEXT, Type INT, ",
+            "VerifyFlag INT, ExtraBuf BLOB);",
+            // 性别为女, 个性签名为 你好, 地区为 CN Guangdong
+            "INSERT INTO Contact VALUES ('wxid_a', 'alice', '客户A', '昵称A', '1,2,', 3, 0, X'",
+            "74752C06040200000046CF10C41806000000604F7D590000A4D9024A1702000000434EE2EAA8D117090000004775616E67646F6E67');",
+            "INSERT INTO Contact VALUES ('123@chatroom', NULL, NULL, '群聊', NULL, 2, 0, NULL);",
+            "INSERT INTO Contact VALUES ('gh_abc', '', '', '公众号', '', 3, 8, NULL);",
+            "INSERT INTO Contact VALUES ('wxid_b', '', '', '陌生人', '', 4, 0, NULL);",
+            "CREATE TABLE ContactLabel (LabelId INT, LabelName TEXT);",
+            "INSERT INTO ContactLabel VALUES (1, '重要'), (2, '北京');",
+            "CREATE TABLE ContactHeadImgUrl (usrName TEXT, smallHeadImgUrl TEXT, bigHeadImgUrl TEXT);",
+            "INSERT INTO ContactHeadImgUrl VALUES ('wxid_a', 'http://small', '');",
+        ),
+    )
+    .await;
+    let export = WxExport { db: root.clone(), ..Default::default() };
+    let contacts = export.read_contacts().await.unwrap();
+    let kinds: Vec<_> = contacts.iter().map(|c| c.kind).collect();
+    assert_eq!(kinds, [ContactKind::Friend, ContactKind::ChatRoom, ContactKind::OfficialAccount, ContactKind::Other]);
+    let alice = &contacts[0];
+    assert_eq!((alice.alias.as_str(), alice.remark.as_str(), alice.nickname.as_str()), ("alice", "客户A", "昵称A"));
+    assert_eq!(alice.labels, ["重要", "北京"]);
+    assert_eq!((alice.gender, alice.region.as_str(), alice.signature.as_str()), (2, "CN Guangdong", "你好"));
+    assert_eq!(alice.head_image_url, "http://small");
+    export.export_contacts().await.unwrap();
+    let csv = std::fs::read_to_string(root.join("Contact.csv")).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[1].contains("wxid_a,alice,客户A,昵称A,重要;北京,好友,女,\"CN Guangdong\",你好,http://small"));
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(root.join("Contact.json")).unwrap()).unwrap();
+    assert_eq!(json[2]["kind"], "official_account");
+    assert_eq!(json[0]["labels"][1], "北京");
+}
+
+#[tokio::test]
+async fn export_chatrooms() {
+    let root = temp_dir("export_chatrooms");
+    execute(
+        &root.join("MicroMsg.db"),
+        concat!(
+            "CREATE TABLE Contact (UserName TEXT, NickName TEXT);",
+            "INSERT INTO Contact VALUES ('123@chatroom', '项目群');",
+            "CREATE TABLE ChatRoom (ChatRoomName TEXT, UserNameList TEXT, Reserved2 TEXT, RoomData BLOB);",
+            // wxid_b 的群昵称为 小B, wxid_c 没有设置群昵称
+            "INSERT INTO ChatRoom VALUES ('123@chatroom', 'wxid_b^Gwxid_c', 'wxid_b', ",
+            "X'0A100A06777869645F621204E5B08F4218000A0A0A06777869645F6318001000');",
+            // 旧版本没有 RoomData
+            "INSERT INTO ChatRoom VALUES ('456@chatroom', 'wxid_d^Gwxid_e', NULL, NULL);",
+            "CREATE TABLE ChatRoomInfo (ChatRoomName TEXT, Announcement TEXT, AnnouncementEditor TEXT);",
+            "INSERT INTO ChatRoomInfo VALUES ('123@chatroom', '周五发版', 'wxid_b');",
+        ),
+    )
+    .await;
+    let export = WxExport { db: root.clone(), ..Default::default() };
+    let rooms = export.read_chatrooms().await.unwrap();
+    assert_eq!(rooms.len(), 2);
+    let room = &rooms[0];
+    assert_eq!((room.name.as_str(), room.owner.as_str(), room.announcement.as_str()), ("项目群", "wxid_b", "周五发版"));
+    assert_eq!(room.display_name("wxid_b"), Some("小B"));
+    assert_eq!(room.display_name("wxid_c"), None);
+    assert_eq!(
+        rooms[1].members,
+        [
+            ChatRoomMember { wxid: "wxid_d".to_string(), display_name: String::new() },
+            ChatRoomMember { wxid: "wxid_e".to_string(), display_name: String::new() }
+        ]
+    );
+    export.export_chatrooms().await.unwrap();
+    let csv = std::fs::read_to_string(root.join("ChatRoom.csv")).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[1].contains("123@chatroom,项目群,wxid_b,2,周五发版,wxid_b,小B"));
+    let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(root.join("ChatRoom.json")).unwrap()).unwrap();
+    assert_eq!(json[0]["member_count"], 2);
+    assert_eq!(json[0]["named_member_count"], 1);
+    assert_eq!(json[0]["members"][0]["display_name"], "小B");
+}
@@ -0,0 +1,35 @@
+use crate::fixtures::{sample_database, temp_dir};
+use std::path::PathBuf;
+use wx_core::{CipherProfile, WxAccounts, WxEncryptor};
+
+const KEY: [u8; 32] = *b"0123456789abcdef0123456789ABCDEF";
+
+#[tokio::test]
+async fn discover_accounts() {
+    let root = temp_dir("accounts");
+    let wechat = root.join("WeChat Files");
+    for dir in ["All Users/config", "Applet", "wxid_a/Msg/Multi", "wxid_b/config", "not_account"] {
+        std::fs::create_dir_all(wechat.join(dir)).unwrap();
+    }
+    let mut config = b"\x0a\x40C:\\Users\\test\\Documents\\WeChat Files\\wxid_b\\config\\AccInfo.dat\x12".to_vec();
+    config.extend(b"wxid_a");
+    std::fs::write(wechat.join("All Users/config/config.data"), config).unwrap();
+    let plain = root.join("plain.db");
+    sample_database(&plain, CipherProfile::WECHAT_V3.reserve as u8, 10).await;
+    let encryptor =
+        WxEncryptor { source_path: plain, output_path: wechat.join("wxid_a/Msg/MicroMsg.db"), key: KEY, ..Default::default() };
+    encryptor.encrypt().await.unwrap();
+    std::fs::write(wechat.join("wxid_a/Msg/Multi/MSG0.db"), [0u8; 100]).unwrap();
+
+    let found = WxAccounts::discover(&Some(wechat.to_string_lossy().to_string()), &[[7; 32], KEY]).unwrap();
+    assert_eq!(found.last_login.as_deref(), Some("wxid_b"));
+    let names: Vec<_> = found.accounts.iter().map(|a| (a.wxid.as_str(), a.last_login, a.key_known)).collect();
+    assert_eq!(names, [("wxid_a", false, true), ("wxid_b", true, false)]);
+    let a = &found.accounts[0];
+    let databases: Vec<_> = a.databases.iter().map(|(file, _)| file.clone()).collect();
+    assert_eq!(databases, [PathBuf::from("MicroMsg.db"), PathBuf::from("Multi/MSG0.db")]);
+    assert_eq!(a.total_size(), std::fs::metadata(&encryptor.output_path).unwrap().len() + 100);
+    assert!(found.accounts[1].msg_path.is_none());
+    let without_key = WxAccounts::discover(&Some(wechat.to_string_lossy().to_string()), &[]).unwrap();
+    assert!(without_key.accounts.iter().all(|a| !a.key_known));
+}
@@ -0,0 +1,571 @@
+use crate::fixtures::{empty_database, insert_messages, read_messages, sample_database, temp_dir, wal_file};
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::channel,
+    },
+};
+use wx_core::{CipherProfile, DecryptedReader, WxDecryptProgress, WxDecryptStatus, WxDecryptor, WxEncryptor};
+
+const KEY: [u8; 32] = *b"0123456789abcdef0123456789ABCDEF";
+const SALT: [u8; 16] = *b"wx-decrypt-salt!";
+const PAGES: usize = 3;
+
+/// 明文数据库, 第一页是空数据库的文件头, 之后的页填充固定的内容
+fn plain_database(profile: &CipherProfile, pages: usize) -> Vec<u8> {
+    let mut plain = empty_database(profile.page_size, profile.reserve as u8);
+    plain.extend((profile.page_size..profile.page_size * pages).map(|i| (i * 31 % 251) as u8));
+    plain
+}
+
+/// 用 [`WxEncryptor`] 加密, 盐固定, 因此不同的明文加密出的页可以拼在一起
+async fn encrypt_database(profile: &CipherProfile, plain: &[u8]) -> Vec<u8> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let root = temp_dir(&format!("decrypt_encrypted/{}", COUNTER.fetch_add(1, Ordering::Relaxed)));
+    std::fs::write(root.join("plain.db"), plain).unwrap();
+    let encryptor = WxEncryptor {
+        source_path: root.join("plain.db"),
+        output_path: root.join("encrypted.db"),
+        key: KEY,
+        cipher: Some(*profile),
+        salt: Some(SALT),
+    };
+    encryptor.encrypt().await.unwrap();
+    std::fs::read(&encryptor.output_path).unwrap()
+}
+
+/// 数据库中的第 `index` 页
+fn page_of(profile: &CipherProfile, data: &[u8], index: u32) -> Vec<u8> {
+    data[(index as usize - 1) * profile.page_size..index as usize * profile.page_size].to_vec()
+}
+
+fn prepare_source(name: &str, encrypted: &[u8], wal: &[u8]) -> (PathBuf, PathBuf) {
+    let root = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&root);
+    let (source, output) = (root.join("Msg"), root.join("decrypted"));
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::create_dir_all(output.join("Multi")).unwrap();
+    std::fs::write(source.join("MicroMsg.db"), encrypted).unwrap();
+    if !wal.is_empty() {
+        std::fs::write(source.join("MicroMsg.db-wal"), wal).unwrap();
+        std::fs::write(source.join("MicroMsg.db-shm"), [0u8; 32]).unwrap();
+    }
+    (source, output)
+}
+
+/// 解密结果的保留区是原样写回的密文, 只比较其余部分
+fn assert_same_content(profile: &CipherProfile, decrypted: &Path, plain: &[u8]) {
+    let decrypted = std::fs::read(decrypted).unwrap();
+    assert_eq!(decrypted.len(), plain.len());
+    for (a, b) in decrypted.chunks(profile.page_size).zip(plain.chunks(profile.page_size)) {
+        assert_eq!(a[..a.len() - profile.reserve], b[..b.len() - profile.reserve]);
+    }
+}
+
+/// WAL 帧头的前 16 个字节原样保留, 页数据与明文一致
+fn assert_same_wal(profile: &CipherProfile, decrypted: &Path, wal: &[u8], plain: &[u8]) {
+    let decrypted = std::fs::read(decrypted).unwrap();
+    assert_eq!(decrypted.len(), wal.len());
+    assert_eq!(decrypted[..32], wal[..32]);
+    for (a, b) in decrypted[32..].chunks(24 + profile.page_size).zip(wal[32..].chunks(24 + profile.page_size)) {
+        assert_eq!(a[..16], b[..16]);
+        let page = u32::from_be_bytes(a[0..4].try_into().unwrap()) as usize;
+        let expect = &plain[(page - 1) * profile.page_size..page * profile.page_size];
+        assert_eq!(a[24..a.len() - profile.reserve], expect[..expect.len() - profile.reserve]);
+    }
+}
+
+async fn round_trip(name: &str, profile: CipherProfile) {
+    let plain = plain_database(&profile, PAGES);
+    let encrypted = encrypt_database(&profile, &plain).await;
+    let frames = [(2, page_of(&profile, &encrypted, 2), 0), (3, page_of(&profile, &encrypted, 3), PAGES as u32)];
+    let wal = wal_file(profile.page_size, &frames);
+    assert_eq!(CipherProfile::detect(&KEY, &encrypted), Some(profile));
+    let (source_path, output_path) = prepare_source(name, &encrypted, &wal);
+    let decryptor = WxDecryptor { source_path, output_path, key: KEY, need_check_hmac: true, ..Default::default() };
+    decryptor.decrypt().await.unwrap();
+    assert_same_content(&profile, &decryptor.output_path.join("MicroMsg.db"), &plain);
+    assert_same_wal(&profile, &decryptor.output_path.join("MicroMsg.db-wal"), &wal, &plain);
+    assert!(decryptor.output_path.join("MicroMsg.db-shm").exists());
+}
+
+#[tokio::test]
+async fn round_trip_v3() {
+    round_trip("decrypt_v3", CipherProfile::WECHAT_V3).await
+}
+
+#[tokio::test]
+async fn round_trip_v4() {
+    round_trip("decrypt_v4", CipherProfile::WECHAT_V4).await
+}
+
+#[tokio::test]
+async fn truncated_wal() {
+    let profile = CipherProfile::WECHAT_V3;
+    let plain = plain_database(&profile, PAGES);
+    let encrypted = encrypt_database(&profile, &plain).await;
+    let frames = [(2, page_of(&profile, &encrypted, 2), 0), (3, page_of(&profile, &encrypted, 3), PAGES as u32)];
+    let wal = wal_file(profile.page_size, &frames);
+    // 末尾写到一半的帧, 分别短于帧头和短于整个帧
+    for (name, tail) in [("decrypt_torn_header", 10), ("decrypt_torn_frame", 24 + 100)] {
+        let mut torn = wal.clone();
+        torn.extend(&wal[32..32 + tail]);
+        let (source_path, output_path) = prepare_source(name, &encrypted, &torn);
+        let decryptor = WxDecryptor { source_path, output_path, key: KEY, need_check_hmac: true, ..Default::default() };
+        let report = decryptor.decrypt().await.unwrap();
+        assert_eq!(report.files[0].status, WxDecryptStatus::Decrypted);
+        assert_eq!(report.files[0].wal_frames, 2);
+        assert_eq!(report.files[0].warnings.len(), 1);
+        assert_same_wal(&profile, &decryptor.output_path.join("MicroMsg.db-wal"), &wal, &plain);
+    }
+}
+
+#[tokio::test]
+async fn reject_wrong_profile() {
+    let profile = CipherProfile::WECHAT_V3;
+    let encrypted = encrypt_database(&profile, &plain_database(&profile, PAGES)).await;
+    assert_eq!(CipherProfile::detect(&[7; 32], &encrypted), None);
+    let (source_path, output_path) = prepare_source("decrypt_wrong_profile", &encrypted, &[]);
+    let decryptor = WxDecryptor {
+        source_path,
+        output_path,
+        key: KEY,
+        need_check_hmac: true,
+        cipher: Some(CipherProfile::WECHAT_V4),
+        ..Default::default()
+    };
+    let report = decryptor.decrypt().await.unwrap();
+    assert!(!report.is_success());
+    assert_eq!(report.files[0].status, WxDecryptStatus::WrongKey);
+}
+
+#[tokio::test]
+async fn reject_invalid_profile() {
+    for profile in CipherProfile::BUILTIN {
+        profile.validate().unwrap();
+    }
+    let profile = CipherProfile::WECHAT_V3;
+    let encrypted = encrypt_database(&profile, &plain_database(&profile, PAGES)).await;
+    for (name, invalid) in [
+        ("decrypt_short_reserve", CipherProfile { reserve: 16, ..profile }),
+        ("decrypt_large_reserve", CipherProfile { reserve: 8192, ..profile }),
+    ] {
+        assert!(invalid.validate().is_err());
+        let (source_path, output_path) = prepare_source(name, &encrypted, &[]);
+        let decryptor = WxDecryptor {
+            source_path,
+            output_path,
+            key: KEY,
+            need_check_hmac: true,
+            cipher: Some(invalid),
+            ..Default::default()
+        };
+        let report = decryptor.decrypt().await.unwrap();
+        assert!(matches!(report.files[0].status, WxDecryptStatus::IoError { .. }));
+    }
+}
+
+#[tokio::test]
+async fn decrypt_report() {
+    let profile = CipherProfile::WECHAT_V3;
+    let plain = plain_database(&profile, PAGES);
+    let encrypted = encrypt_database(&profile, &plain).await;
+    let (source_path, output_path) = prepare_source("decrypt_report", &encrypted, &[]);
+    let mut tampered = encrypted.clone();
+    tampered[2 * profile.page_size + 100] ^= 0xFF;
+    std::fs::write(source_path.join("Tampered.db"), &tampered).unwrap();
+    std::fs::write(source_path.join("Plain.db"), &plain).unwrap();
+    let decryptor = WxDecryptor { source_path, output_path, key: KEY, need_check_hmac: true, ..Default::default() };
+    let report = decryptor.decrypt().await.unwrap();
+    let status: Vec<_> = report.files.iter().map(|f| (f.file.to_str().unwrap(), f.status.clone())).collect();
+    assert_eq!(
+        status,
+        [
+            ("MicroMsg.db", WxDecryptStatus::Decrypted),
+            ("Plain.db", WxDecryptStatus::NotEncrypted),
+            ("Tampered.db", WxDecryptStatus::HmacFailure { page: 3 }),
+        ]
+    );
+    assert_eq!(report.files[0].pages, PAGES as u64);
+    assert_eq!(report.count(|s| matches!(s, WxDecryptStatus::Decrypted)), 1);
+    assert!(!report.is_success());
+    assert!(!decryptor.output_path.join("Plain.db").exists());
+}
+
+#[tokio::test]
+async fn parallel_decrypt() {
+    let profile = CipherProfile::WECHAT_V3;
+    let plain = plain_database(&profile, 600);
+    let encrypted = encrypt_database(&profile, &plain).await;
+    let (source_path, _) = prepare_source(
+        "decrypt_parallel",
+        &encrypted,
+        &wal_file(
+            profile.page_size,
+            &[(5, page_of(&profile, &encrypted, 5), 0), (300, page_of(&profile, &encrypted, 300), 600)],
+        ),
+    );
+    std::fs::create_dir_all(source_path.join("Multi")).unwrap();
+    for i in 0..3 {
+        std::fs::write(source_path.join(format!("Multi/MSG{}.db", i)), &encrypted).unwrap();
+    }
+    let root = source_path.parent().unwrap();
+    let mut outputs = vec![];
+    for threads in [1, 4] {
+        let (sender, receiver) = channel();
+        let decryptor = WxDecryptor {
+            source_path: source_path.clone(),
+            output_path: root.join(format!("threads_{}", threads)),
+            key: KEY,
+            need_check_hmac: true,
+            threads,
+            progress: Some(sender),
+            ..Default::default()
+        };
+        decryptor.decrypt().await.unwrap();
+        drop(decryptor);
+        let progress: Vec<WxDecryptProgress> = receiver.into_iter().collect();
+        for file in ["MicroMsg.db", "Multi/MSG0.db", "Multi/MSG1.db", "Multi/MSG2.db"] {
+            let last = progress.iter().rfind(|p| p.file == Path::new(file)).unwrap();
+            assert_eq!(last.bytes_done, last.bytes_total);
+        }
+        outputs.push(root.join(format!("threads_{}", threads)));
+    }
+    for file in ["MicroMsg.db", "MicroMsg.db-wal", "Multi/MSG0.db", "Multi/MSG2.db"] {
+        assert_eq!(std::fs::read(outputs[0].join(file)).unwrap(), std::fs::read(outputs[1].join(file)).unwrap());
+    }
+    assert_same_content(&profile, &outputs[1].join("Multi/MSG1.db"), &plain);
+}
+
+#[tokio::test]
+async fn decrypt_selected_files() {
+    let profile = CipherProfile::WECHAT_V3;
+    let plain = plain_database(&profile, PAGES);
+    let encrypted = encrypt_database(&profile, &plain).await;
+    let (source_path, output_path) = prepare_source("decrypt_selected_files", &encrypted, &[]);
+    std::fs::create_dir_all(source_path.join("Multi")).unwrap();
+    std::fs::write(source_path.join("Multi/MSG0.db"), &encrypted).unwrap();
+    let decryptor = WxDecryptor {
+        source_path,
+        output_path,
+        key: KEY,
+        files: vec![PathBuf::from("Multi/MSG0.db"), PathBuf::from("Missing.db")],
+        ..Default::default()
+    };
+    let report = decryptor.decrypt().await.unwrap();
+    let status: Vec<_> = report.files.iter().map(|f| (f.file.to_str().unwrap(), f.status.is_success())).collect();
+    assert_eq!(status, [("Missing.db", false), ("Multi/MSG0.db", true)]);
+    assert_same_content(&profile, &decryptor.output_path.join("Multi/MSG0.db"), &plain);
+    assert!(!decryptor.output_path.join("MicroMsg.db").exists());
+}
+
+/// 真实的数据库, WAL 中提交了插入的消息, 返回加密数据库文件夹, 解密文件夹和插入后的明文数据库
+async fn sample_with_wal(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+    let profile = CipherProfile::WECHAT_V3;
+    let root = temp_dir(name);
+    let (before, after) = (root.join("before.db"), root.join("after.db"));
+    sample_database(&before, profile.reserve as u8, 100).await;
+    std::fs::copy(&before, &after).unwrap();
+    insert_messages(&after, 100..250).await;
+    let (plain_before, plain_after) = (std::fs::read(&before).unwrap(), std::fs::read(&after).unwrap());
+    let (encrypted_before, encrypted_after) =
+        (encrypt_database(&profile, &plain_before).await, encrypt_database(&profile, &plain_after).await);
+    let total = (plain_after.len() / profile.page_size) as u32;
+    // 第一页先写入旧的内容, 后面的帧会覆盖它
+    let mut frames = vec![(1, page_of(&profile, &encrypted_before, 1), 0)];
+    for index in 1..=total {
+        let page = page_of(&profile, &plain_after, index);
+        if plain_before.get((index as usize - 1) * profile.page_size..index as usize * profile.page_size) != Some(&page) {
+            frames.push((index, page_of(&profile, &encrypted_after, index), 0));
+        }
+    }
+    frames.last_mut().unwrap().2 = total;
+    // 未提交的帧不会写入
+    frames.push((2, page_of(&profile, &encrypted_before, 2), 0));
+    let mut wal = wal_file(profile.page_size, &frames);
+    // 盐不匹配的帧之后的内容都会被忽略
+    let mut bad_salt = wal[wal.len() - 24 - profile.page_size..].to_vec();
+    bad_salt[8] ^= 0xFF;
+    bad_salt[4..8].copy_from_slice(&total.to_be_bytes());
+    wal.extend(bad_salt);
+    let (source_path, output_path) = prepare_source(&format!("{}/wechat", name), &encrypted_before, &wal);
+    (source_path, output_path, after)
+}
+
+#[tokio::test]
+async fn merge_wal_frames() {
+    let profile = CipherProfile::WECHAT_V3;
+    let (source_path, output_path, after) = sample_with_wal("decrypt_merge_wal").await;
+    let plain_after = std::fs::read(&after).unwrap();
+    let decryptor =
+        WxDecryptor { source_path, output_path, key: KEY, need_check_hmac: true, merge_wal: true, ..Default::default() };
+    decryptor.decrypt().await.unwrap();
+    let merged = decryptor.output_path.join("MicroMsg.db");
+    assert_same_content(&profile, &merged, &plain_after);
+    assert!(!decryptor.output_path.join("MicroMsg.db-wal").exists());
+    assert!(!decryptor.output_path.join("MicroMsg.db-shm").exists());
+    assert_eq!(read_messages(&merged).await, read_messages(&after).await);
+}
+
+#[tokio::test]
+async fn decrypt_in_memory() {
+    let (source_path, output_path, after) = sample_with_wal("decrypt_in_memory").await;
+    let decryptor =
+        WxDecryptor { source_path, output_path, key: KEY, need_check_hmac: true, merge_wal: true, ..Default::default() };
+    let data = decryptor.decrypt_in_memory(Path::new("MicroMsg.db")).unwrap();
+    decryptor.decrypt().await.unwrap();
+    assert_eq!(data, std::fs::read(decryptor.output_path.join("MicroMsg.db")).unwrap());
+    let pool = decryptor.connect_in_memory(Path::new("MicroMsg.db")).await.unwrap();
+    let rows: Vec<(i64, String, String)> =
+        sqlx::query_as("SELECT localId, StrTalker, StrContent FROM MSG ORDER BY localId").fetch_all(&pool).await.unwrap();
+    assert_eq!(rows, read_messages(&after).await);
+}
+
+#[tokio::test]
+async fn plain_in_memory_with_wal() {
+    let page_size = 4096;
+    let root = temp_dir("decrypt_plain_in_memory");
+    let (before, after) = (root.join("before.db"), root.join("after.db"));
+    sample_database(&before, 0, 100).await;
+    std::fs::copy(&before, &after).unwrap();
+    insert_messages(&after, 100..250).await;
+    let (plain_before, plain_after) = (std::fs::read(&before).unwrap(), std::fs::read(&after).unwrap());
+    let total = (plain_after.len() / page_size) as u32;
+    let mut frames = vec![];
+    for index in 1..=total {
+        let page = plain_after[(index as usize - 1) * page_size..index as usize * page_size].to_vec();
+        if plain_before.get((index as usize - 1) * page_size..index as usize * page_size) != Some(&page) {
+            frames.push((index, page, 0));
+        }
+    }
+    frames.last_mut().unwrap().2 = total;
+    let (source_path, output_path) =
+        prepare_source("decrypt_plain_in_memory/wechat", &plain_before, &wal_file(page_size, &frames));
+    let decryptor = WxDecryptor { source_path, output_path, key: KEY, ..Default::default() };
+    assert_eq!(decryptor.decrypt_in_memory(Path::new("MicroMsg.db")).unwrap(), plain_after);
+    let pool = decryptor.connect_in_memory(Path::new("MicroMsg.db")).await.unwrap();
+    let rows: Vec<(i64, String, String)> =
+        sqlx::query_as("SELECT localId, StrTalker, StrContent FROM MSG ORDER BY localId").fetch_all(&pool).await.unwrap();
+    assert_eq!(rows, read_messages(&after).await);
+}
+
+#[tokio::test]
+async fn verify_key() {
+    let v3 = encrypt_database(&CipherProfile::WECHAT_V3, &plain_database(&CipherProfile::WECHAT_V3, 2)).await;
+    let (source_path, _) = prepare_source("decrypt_verify_key", &v3, &[]);
+    std::fs::create_dir_all(source_path.join("Multi")).unwrap();
+    let v4 = encrypt_database(&CipherProfile::WECHAT_V4, &plain_database(&CipherProfile::WECHAT_V4, 2)).await;
+    std::fs::write(source_path.join("Multi/MSG0.db"), v4).unwrap();
+    std::fs::write(source_path.join("Plain.db"), plain_database(&CipherProfile::WECHAT_V3, 1)).unwrap();
+    let micro_msg = source_path.join("MicroMsg.db");
+    assert_eq!(WxDecryptor::verify_key(&micro_msg, &KEY).unwrap(), Some(CipherProfile::WECHAT_V3));
+    assert_eq!(WxDecryptor::verify_key(&micro_msg, &[7; 32]).unwrap(), None);
+    let results = WxDecryptor::verify_key_tree(&source_path, &KEY);
+    let results: Vec<_> = results.iter().map(|(file, r)| (file.to_str().unwrap(), r.as_ref().ok().copied())).collect();
+    assert_eq!(
+        results,
+        [
+            ("MicroMsg.db", Some(Some(CipherProfile::WECHAT_V3))),
+            ("Multi/MSG0.db", Some(Some(CipherProfile::WECHAT_V4))),
+            ("Plain.db", None),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn decrypted_reader() {
+    let profile = CipherProfile::WECHAT_V4;
+    let size = profile.page_size;
+    let encrypted = encrypt_database(&profile, &plain_database(&profile, 5)).await;
+    let (source_path, output_path) = prepare_source("decrypt_reader", &encrypted, &[]);
+    let decryptor = WxDecryptor { source_path, output_path, key: KEY, ..Default::default() };
+    decryptor.decrypt().await.unwrap();
+    let expect = std::fs::read(decryptor.output_path.join("MicroMsg.db")).unwrap();
+    assert!(DecryptedReader::new(Cursor::new(encrypted.clone()), [7; 32]).is_err());
+    assert!(DecryptedReader::with_profile(Cursor::new(encrypted.clone()), KEY, CipherProfile::WECHAT_V3).is_err());
+    let mut reader = DecryptedReader::new(Cursor::new(encrypted.clone()), KEY).unwrap();
+    assert_eq!(reader.profile(), profile);
+    reader.set_cache_pages(2);
+    let mut all = vec![];
+    reader.read_to_end(&mut all).unwrap();
+    assert_eq!(all, expect);
+    // 跨页读取, 以及回到第一页读取文件头
+    for (start, length) in [(size as u64 - 10, 30), (2 * size as u64 + 7, 2 * size + 5), (0, 16)] {
+        let mut buffer = vec![0u8; length];
+        reader.seek(SeekFrom::Start(start)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, expect[start as usize..start as usize + length]);
+    }
+    let mut tail = vec![];
+    reader.seek(SeekFrom::End(-5)).unwrap();
+    reader.read_to_end(&mut tail).unwrap();
+    assert_eq!(tail, expect[expect.len() - 5..]);
+    // 校验失败的页只在读取时报错
+    let mut tampered = encrypted;
+    tampered[2 * size + 100] ^= 0xFF;
+    let mut reader = DecryptedReader::new(Cursor::new(tampered), KEY).unwrap();
+    let mut buffer = vec![0u8; 10];
+    reader.read_exact(&mut buffer).unwrap();
+    reader.seek(SeekFrom::Start(2 * size as u64)).unwrap();
+    assert!(reader.read_exact(&mut buffer).is_err());
+    // 跨过校验失败的页时先返回之前的数据
+    reader.seek(SeekFrom::Start(2 * size as u64 - 5)).unwrap();
+    assert_eq!(reader.read(&mut buffer).unwrap(), 5);
+    assert_eq!(buffer[..5], expect[2 * size - 5..2 * size]);
+    assert!(reader.read(&mut buffer).is_err());
+    reader.set_check_hmac(false);
+    reader.seek(SeekFrom::Start(2 * size as u64 - 5)).unwrap();
+    reader.read_exact(&mut buffer).unwrap();
+    assert_eq!(buffer[..5], expect[2 * size - 5..2 * size]);
+}
+
+/// 修改数据库和追加 WAL 帧之后增量解密, 结果和完整解密一致
+async fn incremental(name: &str, merge_wal: bool) {
+    let profile = CipherProfile::WECHAT_V3;
+    let size = profile.page_size;
+    let plain = plain_database(&profile, 8);
+    let encrypted = encrypt_database(&profile, &plain).await;
+    let frames = vec![(2, page_of(&profile, &encrypted, 2), 0), (3, page_of(&profile, &encrypted, 3), 8)];
+    let (source_path, output_path) = prepare_source(name, &encrypted, &wal_file(profile.page_size, &frames));
+    let decryptor = |output_path: PathBuf, incremental: bool| WxDecryptor {
+        source_path: source_path.clone(),
+        output_path,
+        key: KEY,
+        need_check_hmac: true,
+        merge_wal,
+        incremental,
+        ..Default::default()
+    };
+    let report = decryptor(output_path.clone(), true).decrypt().await.unwrap();
+    assert_eq!(report.files[0].status, WxDecryptStatus::Decrypted);
+    let report = decryptor(output_path.clone(), true).decrypt().await.unwrap();
+    assert_eq!(report.files[0].status, WxDecryptStatus::Unchanged);
+    assert_eq!(report.files[0].pages_written, 0);
+    // 修改第 5 页并追加两页, WAL 中追加修改第 7 页的帧
+    let mut changed = plain.clone();
+    changed[4 * size + 100] ^= 0xFF;
+    changed.extend_from_slice(&plain[4 * size..6 * size]);
+    // 没有变化的页保留原来的密文
+    let mut changed_encrypted = encrypt_database(&profile, &changed).await;
+    changed_encrypted[..4 * size].copy_from_slice(&encrypted[..4 * size]);
+    changed_encrypted[5 * size..8 * size].copy_from_slice(&encrypted[5 * size..8 * size]);
+    let mut updated = changed.clone();
+    updated[6 * size + 100] ^= 0xFF;
+    let updated_encrypted = encrypt_database(&profile, &updated).await;
+    let mut frames = frames;
+    frames.push((7, page_of(&profile, &updated_encrypted, 7), 10));
+    std::fs::write(source_path.join("MicroMsg.db"), &changed_encrypted).unwrap();
+    std::fs::write(source_path.join("MicroMsg.db-wal"), wal_file(profile.page_size, &frames)).unwrap();
+    let report = decryptor(output_path.clone(), true).decrypt().await.unwrap();
+    assert_eq!(report.files[0].status, WxDecryptStatus::Decrypted);
+    match merge_wal {
+        true => assert_eq!(report.files[0].pages_written, 4),
+        false => {
+            assert_eq!(report.files[0].pages_written, 3);
+            assert_eq!(report.files[0].wal_frames, 1);
+        }
+    }
+    let full = output_path.with_file_name("full");
+    decryptor(full.clone(), false).decrypt().await.unwrap();
+    for file in ["MicroMsg.db", "MicroMsg.db-wal", "MicroMsg.db-shm"] {
+        assert_eq!(std::fs::read(output_path.join(file)).ok(), std::fs::read(full.join(file)).ok(), "{}", file);
+    }
+}
+
+#[tokio::test]
+async fn incremental_decrypt() {
+    incremental("decrypt_incremental", false).await
+}
+
+#[tokio::test]
+async fn incremental_merge_wal() {
+    incremental("decrypt_incremental_merge", true).await
+}
+
+#[tokio::test]
+async fn salvage_database() {
+    let profile = CipherProfile::WECHAT_V3;
+    let root = temp_dir("decrypt_salvage");
+    let plain = root.join("plain.db");
+    sample_database(&plain, profile.reserve as u8, 300).await;
+    let mut encrypted = encrypt_database(&profile, &std::fs::read(&plain).unwrap()).await;
+    // 第三页的 hmac 校验失败, 最后一页只复制了一半
+    encrypted[profile.page_size * 2 + 100] ^= 0xFF;
+    encrypted.truncate(encrypted.len() - profile.page_size / 2);
+    let (source_path, output_path) = prepare_source("decrypt_salvage/wechat", &encrypted, &[]);
+    let decryptor = WxDecryptor { source_path, output_path, key: KEY, salvage: true, ..Default::default() };
+    let report = decryptor.decrypt().await.unwrap();
+    let file = &report.files[0];
+    assert_eq!(file.status, WxDecryptStatus::Salvaged);
+    assert_eq!(file.zeroed_pages, vec![3]);
+    assert_eq!(file.dropped_bytes, profile.page_size as u64 / 2);
+    assert!(!report.is_success());
+    let messages = read_messages(&plain).await;
+    let salvaged = read_messages(&decryptor.output_path.join("MicroMsg.salvage.db")).await;
+    assert!(!salvaged.is_empty() && salvaged.len() < messages.len());
+    assert!(salvaged.iter().all(|row| messages.contains(row)));
+}
+
+#[tokio::test]
+async fn salvage_truncated_wal() {
+    for merge_wal in [false, true] {
+        let (source_path, output_path, after) = sample_with_wal(&format!("decrypt_salvage_wal_{}", merge_wal)).await;
+        // 最后一帧只写入了一部分
+        let file_wal = source_path.join("MicroMsg.db-wal");
+        let mut wal = std::fs::read(&file_wal).unwrap();
+        wal.truncate(wal.len() - 100);
+        std::fs::write(&file_wal, wal).unwrap();
+        let decryptor = WxDecryptor { source_path, output_path, key: KEY, salvage: true, merge_wal, ..Default::default() };
+        let report = decryptor.decrypt().await.unwrap();
+        let file = &report.files[0];
+        assert_eq!(file.status, WxDecryptStatus::Decrypted, "merge_wal: {}", merge_wal);
+        assert!(file.warnings.iter().any(|w| w.contains("不完整")), "{:?}", file.warnings);
+        if merge_wal {
+            assert_eq!(read_messages(&decryptor.output_path.join("MicroMsg.db")).await, read_messages(&after).await);
+        }
+        else {
+            let decrypted = std::fs::read(decryptor.output_path.join("MicroMsg.db-wal")).unwrap();
+            assert_eq!((decrypted.len() - 32) % (24 + CipherProfile::WECHAT_V3.page_size), 0);
+        }
+    }
+}
+
+#[tokio::test]
+async fn salvage_wal_frame_incremental() {
+    let profile = CipherProfile::WECHAT_V3;
+    let plain = plain_database(&profile, 4);
+    let encrypted = encrypt_database(&profile, &plain).await;
+    let mut updated = plain.clone();
+    updated[profile.page_size + 100] ^= 0xFF;
+    let mut frame = page_of(&profile, &encrypt_database(&profile, &updated).await, 2);
+    // WAL 中第 2 页的帧 hmac 校验失败
+    frame[200] ^= 0xFF;
+    let wal = wal_file(profile.page_size, &[(2, frame, 4)]);
+    let (source_path, output_path) = prepare_source("decrypt_salvage_wal_incremental", &encrypted, &wal);
+    let decryptor = |salvage: bool| WxDecryptor {
+        source_path: source_path.clone(),
+        output_path: output_path.clone(),
+        key: KEY,
+        need_check_hmac: true,
+        merge_wal: true,
+        incremental: true,
+        salvage,
+        ..Default::default()
+    };
+    let report = decryptor(true).decrypt().await.unwrap();
+    assert_eq!(report.files[0].pages_written, 4);
+    // 修改数据库的第 3 页, 跳过的帧没有记录标记, 不使用抢救模式时会重新处理并报告错误
+    let mut changed = plain.clone();
+    changed[2 * profile.page_size + 100] ^= 0xFF;
+    let mut changed_encrypted = encrypted.clone();
+    changed_encrypted[2 * profile.page_size..3 * profile.page_size].copy_from_slice(&page_of(
+        &profile,
+        &encrypt_database(&profile, &changed).await,
+        3,
+    ));
+    std::fs::write(source_path.join("MicroMsg.db"), &changed_encrypted).unwrap();
+    let report = decryptor(false).decrypt().await.unwrap();
+    assert_eq!(report.files[0].status, WxDecryptStatus::HmacFailure { page: 2 });
+}
@@ -0,0 +1,105 @@
+use crate::fixtures::{empty_database, read_messages, sample_database, temp_dir};
+use wx_core::{CipherProfile, WxDecryptor, WxEncryptor};
+
+const KEY: [u8; 32] = *b"0123456789abcdef0123456789ABCDEF";
+
+async fn round_trip(name: &str, profile: CipherProfile) {
+    let root = temp_dir(name);
+    let plain = root.join("MicroMsg.db");
+    sample_database(&plain, profile.reserve as u8, 200).await;
+    let encryptor = WxEncryptor {
+        source_path: plain.clone(),
+        output_path: root.join("Msg/MicroMsg.db"),
+        key: KEY,
+        cipher: Some(profile),
+        ..Default::default()
+    };
+    encryptor.encrypt().await.unwrap();
+    let encrypted = std::fs::read(&encryptor.output_path).unwrap();
+    assert_eq!(CipherProfile::detect(&KEY, &encrypted), Some(profile));
+    let decryptor = WxDecryptor {
+        source_path: root.join("Msg"),
+        output_path: root.join("decrypted"),
+        key: KEY,
+        need_check_hmac: true,
+        ..Default::default()
+    };
+    decryptor.decrypt().await.unwrap();
+    let original = std::fs::read(&plain).unwrap();
+    let decrypted = std::fs::read(root.join("decrypted/MicroMsg.db")).unwrap();
+    assert_eq!(original.len(), decrypted.len());
+    // 保留区存放的是 IV 和 hmac, 其余字节应当完全一致
+    for (a, b) in original.chunks(profile.page_size).zip(decrypted.chunks(profile.page_size)) {
+        assert_eq!(a[..a.len() - profile.reserve], b[..b.len() - profile.reserve]);
+    }
+    assert_eq!(read_messages(&root.join("decrypted/MicroMsg.db")).await, read_messages(&plain).await);
+}
+
+#[tokio::test]
+async fn encrypt_round_trip_v3() {
+    round_trip("encrypt_v3", CipherProfile::WECHAT_V3).await
+}
+
+#[tokio::test]
+async fn encrypt_round_trip_v4() {
+    round_trip("encrypt_v4", CipherProfile::WECHAT_V4).await
+}
+
+#[tokio::test]
+async fn encrypt_random_salt() {
+    let root = temp_dir("encrypt_random_salt");
+    std::fs::write(root.join("plain.db"), empty_database(4096, 48)).unwrap();
+    let mut outputs = vec![];
+    for i in 0..2 {
+        let output_path = root.join(format!("{}.db", i));
+        let encryptor = WxEncryptor {
+            source_path: root.join("plain.db"),
+            output_path: output_path.clone(),
+            key: KEY,
+            cipher: None,
+            ..Default::default()
+        };
+        encryptor.encrypt().await.unwrap();
+        outputs.push(std::fs::read(output_path).unwrap());
+    }
+    assert_eq!(outputs[0].len(), 4096);
+    assert_ne!(outputs[0][0..16], outputs[1][0..16]);
+}
+
+#[tokio::test]
+async fn encrypt_fixed_salt() {
+    let root = temp_dir("encrypt_fixed_salt");
+    std::fs::write(root.join("plain.db"), empty_database(4096, 48)).unwrap();
+    let salt = *b"fixed-salt-16byt";
+    let mut outputs = vec![];
+    for i in 0..2 {
+        let output_path = root.join(format!("{}.db", i));
+        let encryptor = WxEncryptor {
+            source_path: root.join("plain.db"),
+            output_path: output_path.clone(),
+            key: KEY,
+            salt: Some(salt),
+            ..Default::default()
+        };
+        encryptor.encrypt().await.unwrap();
+        outputs.push(std::fs::read(output_path).unwrap());
+    }
+    assert_eq!(outputs[0][0..16], salt);
+    assert_eq!(outputs[1][0..16], salt);
+    // IV 仍然是随机的
+    assert_ne!(outputs[0][16..], outputs[1][16..]);
+}
+
+#[tokio::test]
+async fn encrypt_without_reserve() {
+    let root = temp_dir("encrypt_without_reserve");
+    std::fs::write(root.join("plain.db"), empty_database(4096, 0)).unwrap();
+    let encryptor = WxEncryptor {
+        source_path: root.join("plain.db"),
+        output_path: root.join("out.db"),
+        key: KEY,
+        cipher: None,
+        ..Default::default()
+    };
+    assert!(encryptor.encrypt().await.is_err());
+}
@@ -0,0 +1,71 @@
+use crate::fixtures::temp_dir;
+use std::time::Duration;
+use wx_core::WxSnapshot;
+
+#[test]
+fn snapshot_msg_folder() {
+    let root = temp_dir("snapshot");
+    let source = root.join("Msg");
+    std::fs::create_dir_all(source.join("Multi")).unwrap();
+    std::fs::write(source.join("MicroMsg.db"), vec![1u8; 8192]).unwrap();
+    std::fs::write(source.join("MicroMsg.db-wal"), vec![2u8; 100]).unwrap();
+    std::fs::write(source.join("MicroMsg.db-shm"), vec![3u8; 32]).unwrap();
+    std::fs::write(source.join("Multi/MSG0.db"), vec![4u8; 4096]).unwrap();
+    std::fs::write(source.join("Multi/MSG0.db-shm"), vec![5u8; 32]).unwrap();
+    let backup = WxSnapshot::timestamp_dir(&root.join("backup"));
+    let snapshot = WxSnapshot {
+        source_path: source.clone(),
+        output_path: backup.join("wxid_test/Msg"),
+        retry_delay: Duration::ZERO,
+        ..Default::default()
+    };
+    // 上一次快照留下的 WAL 需要删除
+    std::fs::create_dir_all(snapshot.output_path.join("Multi")).unwrap();
+    std::fs::write(snapshot.output_path.join("Multi/MSG0.db-wal"), [0u8; 32]).unwrap();
+    let files = snapshot.snapshot().unwrap();
+    assert_eq!(files.len(), 2);
+    assert!(files.iter().all(|f| f.consistent && f.attempts == 1 && f.error.is_none()));
+    for file in ["MicroMsg.db", "MicroMsg.db-wal", "MicroMsg.db-shm", "Multi/MSG0.db", "Multi/MSG0.db-shm"] {
+        assert_eq!(std::fs::read(source.join(file)).unwrap(), std::fs::read(snapshot.output_path.join(file)).unwrap());
+    }
+    assert!(!snapshot.output_path.join("Multi/MSG0.db-wal").exists());
+}
+
+/// 源 WAL 指向快照中上一次留下的 `-shm`, 第一次复制时会被删除, 相当于复制过程中微信做了检查点
+#[cfg(unix)]
+#[test]
+fn snapshot_retry_changed_source() {
+    let root = temp_dir("snapshot_retry");
+    let source = root.join("Msg");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(source.join("MicroMsg.db"), vec![1u8; 8192]).unwrap();
+    let snapshot = WxSnapshot {
+        source_path: source.clone(),
+        output_path: root.join("backup"),
+        retry_delay: Duration::ZERO,
+        ..Default::default()
+    };
+    std::fs::create_dir_all(&snapshot.output_path).unwrap();
+    std::fs::write(snapshot.output_path.join("MicroMsg.db-shm"), vec![3u8; 32]).unwrap();
+    std::os::unix::fs::symlink(snapshot.output_path.join("MicroMsg.db-shm"), source.join("MicroMsg.db-wal")).unwrap();
+    let files = snapshot.snapshot().unwrap();
+    assert_eq!(files.len(), 1);
+    assert!(files[0].consistent && files[0].error.is_none());
+    assert_eq!(files[0].attempts, 2);
+    assert!(!snapshot.output_path.join("MicroMsg.db-wal").exists());
+}
+
+/// 每次复制都失败时用完重试次数, 保留最后一次的错误
+#[test]
+fn snapshot_retries_exhausted() {
+    let root = temp_dir("snapshot_exhausted");
+    let source = root.join("Msg");
+    std::fs::create_dir_all(source.join("MicroMsg.db-wal")).unwrap();
+    std::fs::write(source.join("MicroMsg.db"), vec![1u8; 8192]).unwrap();
+    let snapshot =
+        WxSnapshot { source_path: source, output_path: root.join("backup"), retries: 2, retry_delay: Duration::ZERO };
+    let files = snapshot.snapshot().unwrap();
+    assert_eq!(files.len(), 1);
+    assert!(!files[0].consistent && files[0].error.is_some());
+    assert_eq!(files[0].attempts, 3);
+}
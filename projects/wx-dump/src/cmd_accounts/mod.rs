@@ -0,0 +1,56 @@
+use crate::{WxArguments, utils::string_to_u8_vec};
+use clap::Parser;
+use tracing::trace;
+use wx_core::WxAccounts;
+
+#[derive(Clone, Debug, Parser)]
+pub struct RunAccounts {
+    /// 用于检查数据库能否解密的 key，不填写时尝试从微信进程中读取
+    #[arg(short, long)]
+    pub key: Option<String>,
+    /// key的编码格式，可选值：[hex,base64,string]
+    #[arg(short = 'd', long, default_value = "hex")]
+    pub encode: String,
+}
+
+impl RunAccounts {
+    pub fn run(self, c: WxArguments) -> anyhow::Result<()> {
+        let mut keys = vec![];
+        match self.key.as_ref() {
+            Some(key) => match string_to_u8_vec(key, &self.encode)?.get(0..32) {
+                Some(s) => keys.push(s.try_into()?),
+                None => return Err(anyhow::anyhow!("请输入正确的key")),
+            },
+            None => match c.open_memory() {
+                Ok(o) => keys.push(o.profile().aes256),
+                Err(e) => trace!("无法从微信进程中读取 key: {}", e),
+            },
+        }
+        let found = WxAccounts::discover(&c.wechat_path, &keys)?;
+        println!("微信文件夹: {}", found.wechat_path.display());
+        if let Some(path) = found.custom_path.as_ref() {
+            println!("自定义存储位置: {}", path.display());
+        }
+        println!("最后登录: {}", found.last_login.as_deref().unwrap_or("未知"));
+        for account in &found.accounts {
+            println!();
+            println!(
+                "{}{}: {} 个数据库, {:.2} MB, 秘钥{}",
+                account.wxid,
+                if account.last_login { " (最后登录)" } else { "" },
+                account.databases.len(),
+                account.total_size() as f64 / 1048576.0,
+                if account.key_known { "已知" } else { "未知" },
+            );
+            match account.msg_path.as_ref() {
+                Some(_) => {
+                    for (file, size) in &account.databases {
+                        println!("  {:<40} {:>12}", file.display(), size);
+                    }
+                }
+                None => println!("  没有 Msg 文件夹"),
+            }
+        }
+        anyhow::Ok(())
+    }
+}
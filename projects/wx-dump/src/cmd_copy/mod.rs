@@ -1,19 +1,63 @@
-use crate::WxArguments;
+use crate::{DEFAULT_SAVE_DIR, WxArguments};
 use clap::Parser;
-use wx_core::WxScanner;
+use std::{env::current_dir, path::PathBuf, time::Duration};
+use wx_core::{WxSnapshot, helpers::read_database};
 
 #[derive(Clone, Debug, Parser)]
 pub struct RunCopy {
-    /// 登录名，将依次值寻找复制聊天记录数据库
+    /// 登录名，将依次值寻找复制聊天记录数据库，不填写时复制所有账号
     #[arg(short, long)]
     account: Option<String>,
+    /// 备份文件夹，每次复制都会在其中新建一个以时间命名的文件夹
+    #[arg(short, long, value_name = "备份文件夹")]
+    output: Option<String>,
+    /// 复制前后文件不一致时重试的次数
+    #[arg(long, default_value = "5")]
+    retries: usize,
+    /// 每次重试前等待的毫秒数
+    #[arg(long, default_value = "500")]
+    retry_delay: u64,
 }
 
 impl RunCopy {
-    pub fn run(self, c: WxArguments) -> anyhow::Result<()> {
-        let mut wechat_info = WxScanner::default();
-        wechat_info.open_wechat_process(&c.offset_map, &c.process_id, &c.process_name, &c.module_name)?;
-        println!("{:#?}", wechat_info.profile);
-        Ok(())
+    pub async fn run(self, c: WxArguments) -> anyhow::Result<()> {
+        let mut accounts = read_database(&c.wechat_path).await?;
+        if let Some(account) = self.account.as_ref() {
+            accounts.retain(|user, _| user == account);
+            if accounts.is_empty() {
+                return Err(anyhow::anyhow!("找不到账号: {}", account));
+            }
+        }
+        let root = match self.output.as_ref() {
+            Some(s) => PathBuf::from(s),
+            None => current_dir()?.join(DEFAULT_SAVE_DIR).join("backup"),
+        };
+        // 快照的结构和 WeChat Files 相同, 可以直接作为 --wechat-path 解密
+        let backup = WxSnapshot::timestamp_dir(&root);
+        let mut failed = 0;
+        for (user, source_path) in accounts {
+            let snapshot = WxSnapshot {
+                source_path,
+                output_path: backup.join(&user).join("Msg"),
+                retries: self.retries,
+                retry_delay: Duration::from_millis(self.retry_delay),
+            };
+            for file in snapshot.snapshot()? {
+                let status = match (&file.error, file.consistent) {
+                    (Some(e), _) => e.as_str(),
+                    (None, true) => "一致",
+                    (None, false) => "不一致",
+                };
+                println!("{}: {} 次复制, {}", snapshot.output_path.join(&file.file).display(), file.attempts, status);
+                if file.error.is_some() || !file.consistent {
+                    failed += 1;
+                }
+            }
+        }
+        println!("快照路径: {}", backup.display());
+        match failed {
+            0 => anyhow::Ok(()),
+            n => Err(anyhow::anyhow!("{} 个文件没有得到一致的快照", n)),
+        }
     }
 }
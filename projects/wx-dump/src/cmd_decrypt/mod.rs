@@ -1,6 +1,13 @@
-use crate::{WxArguments, utils::string_to_u8_vec};
+use crate::{
+    DEFAULT_SAVE_DIR, WxArguments,
+    utils::{print_report, progress_printer, string_to_u8_vec},
+};
 use clap::Parser;
-use wx_core::WxScanner;
+use std::{
+    env::current_dir,
+    path::{Path, PathBuf},
+};
+use wx_core::{WxDecryptor, helpers::read_database};
 
 #[derive(Clone, Debug, Parser)]
 pub struct RunDecrypt {
@@ -13,30 +20,102 @@ pub struct RunDecrypt {
     #[arg(long, default_value = "false")]
     /// 是否在解密时检查hmac
     pub check_hmac: bool,
+    /// 把 WAL 中已提交的帧合并进数据库
+    #[arg(long, default_value = "false")]
+    pub merge_wal: bool,
+    /// 抢救模式, 校验失败的页替换为全零的页, 并从中抢救 MSG 表的记录
+    #[arg(long, default_value = "false")]
+    pub salvage: bool,
+}
+
+/// 一次解密任务, 对应一个账号或者单个文件
+struct DecryptTask {
+    source_path: PathBuf,
+    output_path: PathBuf,
+    files: Vec<PathBuf>,
 }
 
 impl RunDecrypt {
-    pub fn run(&self, c: WxArguments) -> anyhow::Result<()> {
-        let mut wechat_info = WxScanner::default();
-        if let Some(key) = self.key.as_ref() {
-            let key_vec = string_to_u8_vec(&key, &self.encode)?;
-            if key_vec.len() < 32 {
-                return Err(anyhow::anyhow!("请输入正确的key"));
+    pub async fn run(&self, c: WxArguments) -> anyhow::Result<()> {
+        let key = match self.key.as_ref() {
+            Some(key) => {
+                let key_vec = string_to_u8_vec(key, &self.encode)?;
+                match key_vec.get(0..32) {
+                    Some(s) => s.try_into()?,
+                    None => return Err(anyhow::anyhow!("请输入正确的key")),
+                }
+            }
+            None => c.open_memory()?.profile().aes256,
+        };
+        let output_root = match c.decrypt_path.as_ref() {
+            Some(s) => PathBuf::from(s),
+            None => current_dir()?.join(DEFAULT_SAVE_DIR),
+        };
+        let mut failed = 0;
+        for task in self.tasks(&c, &output_root).await? {
+            let (sender, printer) = progress_printer();
+            let decryptor = WxDecryptor {
+                source_path: task.source_path,
+                output_path: task.output_path,
+                key,
+                need_check_hmac: self.check_hmac,
+                progress: Some(sender),
+                merge_wal: self.merge_wal,
+                salvage: self.salvage,
+                incremental: c.incremental,
+                files: task.files,
+                ..Default::default()
+            };
+            let report = decryptor.decrypt().await;
+            drop(decryptor);
+            let _ = printer.join();
+            match report {
+                Ok(o) => {
+                    print_report(&o, &c.report)?;
+                    failed += o.count(|status| !status.is_success());
+                }
+                Err(e) => {
+                    println!("{e}");
+                    failed += 1;
+                }
+            }
+        }
+        match failed {
+            0 => anyhow::Ok(()),
+            n => Err(anyhow::anyhow!("{} 个文件解密失败", n)),
+        }
+    }
+    /// 根据 `--wechat-path` 决定解密的范围
+    ///
+    /// 可以是单个数据库文件, 单个账号的文件夹或者它的 `Msg` 文件夹, 也可以是整个 `WeChat Files` 文件夹
+    async fn tasks(&self, c: &WxArguments, output_root: &Path) -> anyhow::Result<Vec<DecryptTask>> {
+        if let Some(path) = c.wechat_path.as_ref().map(PathBuf::from) {
+            if path.is_file() {
+                let (Some(parent), Some(name)) = (path.parent(), path.file_name())
+                else {
+                    return Err(anyhow::anyhow!("无法识别的数据库文件: {}", path.display()));
+                };
+                return Ok(vec![DecryptTask {
+                    source_path: parent.to_path_buf(),
+                    output_path: output_root.to_path_buf(),
+                    files: vec![PathBuf::from(name)],
+                }]);
             }
-            else {
-                wechat_info.profile.aes256 = key_vec[0..32].try_into()?;
+            let account = match (path.join("Msg").is_dir(), path.join("MicroMsg.db").is_file()) {
+                (true, _) => Some((path.join("Msg"), path.file_name())),
+                (_, true) => Some((path.clone(), path.parent().and_then(|p| p.file_name()))),
+                _ => None,
+            };
+            if let Some((source_path, name)) = account {
+                let name = name.map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                return Ok(vec![DecryptTask { source_path, output_path: output_root.join(name), files: vec![] }]);
             }
         }
-        else {
-            wechat_info.open_wechat_process(&c.offset_map, &c.process_id, &c.process_name, &c.module_name)?;
-        };
-        // decrypt(
-        //     &args.save_path,
-        //     &args.decrypt_path,
-        //     &wechat_info.aes256_key,
-        //     check_hmac,
-        //     |s| println!("{s}"),
-        // )?;
-        Ok(())
+        let tasks = read_database(&c.wechat_path)
+            .await?
+            .into_iter()
+            .map(|(user, source_path)| DecryptTask { source_path, output_path: output_root.join(user), files: vec![] })
+            .collect();
+        Ok(tasks)
     }
 }
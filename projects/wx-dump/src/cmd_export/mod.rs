@@ -1,11 +1,8 @@
 use crate::{DEFAULT_SAVE_DIR, WxArguments};
 use clap::Parser;
-use std::{
-    env::current_dir,
-    path::{ PathBuf},
-};
+use std::{env::current_dir, path::PathBuf};
 use tracing::{error, trace};
-use wx_core::{WxExport};
+use wx_core::WxExport;
 
 #[derive(Clone, Debug, Parser)]
 pub struct RunExport {
@@ -35,8 +32,15 @@ impl RunExport {
     }
     pub async fn export_db(&self, _: &WxArguments, dir: PathBuf) -> anyhow::Result<()> {
         trace!("dump file: {}", dir.display());
-        let wx = WxExport { db: dir };
+        let wx = WxExport { db: dir, ..Default::default() };
         wx.export_message().await?;
+        // 旧版本的 MicroMsg.db 可能缺少联系人或群聊的表, 只跳过对应的导出
+        if let Err(e) = wx.export_contacts().await {
+            error!("导出联系人失败: {}, {}", wx.db.display(), e);
+        }
+        if let Err(e) = wx.export_chatrooms().await {
+            error!("导出群聊失败: {}, {}", wx.db.display(), e);
+        }
         Ok(())
     }
 }
@@ -0,0 +1,27 @@
+use crate::{WxArguments, utils::to_hex};
+use clap::Parser;
+use std::path::Path;
+use wx_core::WxKeyChecker;
+
+#[derive(Clone, Debug, Parser)]
+pub struct RunFindKey {
+    /// 用于校验秘钥的加密数据库，一般为 MicroMsg.db
+    #[arg(long, value_name = "数据库")]
+    db: String,
+}
+
+impl RunFindKey {
+    pub fn run(self, c: WxArguments) -> anyhow::Result<()> {
+        let checker = WxKeyChecker::new(Path::new(&self.db))?;
+        let wechat_info = c.open_memory_with_out_info()?;
+        match wechat_info.find_key(&checker)? {
+            Some(found) => {
+                println!("秘钥: {}", to_hex(found.aes256));
+                println!("秘钥地址: {:#x}", found.address);
+                println!("秘钥指针地址: {:#x}", found.pointer);
+                anyhow::Ok(())
+            }
+            None => Err(anyhow::anyhow!("未找到能够解密 {} 的秘钥", self.db)),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::WxArguments;
+use clap::Parser;
+use std::path::Path;
+use wx_core::{WeChatProfile, WxKeyChecker, save_offset_map};
+
+#[derive(Clone, Debug, Parser)]
+pub struct RunOffsets {
+    /// 用于校验秘钥的加密数据库，一般为 MicroMsg.db
+    #[arg(long, value_name = "数据库")]
+    db: String,
+    /// 当前登录账号的昵称
+    #[arg(long)]
+    nick_name: Option<String>,
+    /// 当前登录账号的微信号
+    #[arg(long)]
+    account: Option<String>,
+    /// 当前登录账号绑定的手机号
+    #[arg(long)]
+    phone: Option<String>,
+    /// 当前登录账号绑定的邮箱
+    #[arg(long)]
+    email: Option<String>,
+}
+
+impl RunOffsets {
+    pub fn run(self, c: WxArguments) -> anyhow::Result<()> {
+        let checker = WxKeyChecker::new(Path::new(&self.db))?;
+        let wechat_info = c.open_memory_with_out_info()?;
+        let version = wechat_info.version()?;
+        let known = WeChatProfile {
+            nick_name: self.nick_name.unwrap_or_default(),
+            user_name: self.account.unwrap_or_default(),
+            mobile: self.phone.unwrap_or_default(),
+            email: self.email.unwrap_or_default(),
+            ..Default::default()
+        };
+        let offsets = wechat_info.discover_offsets(&known, &checker)?;
+        println!("微信版本: {}", version);
+        println!("偏移量: {:?}", offsets);
+        if let Some(path) = c.offset_map.as_ref() {
+            save_offset_map(Path::new(path), &version, &offsets)?;
+            println!("已写入: {}", path);
+        }
+        anyhow::Ok(())
+    }
+}
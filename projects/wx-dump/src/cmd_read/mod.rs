@@ -1,6 +1,5 @@
 use crate::{WxArguments, utils::u8_to_string};
 use clap::Parser;
-use wx_core::WxScanner;
 
 #[derive(Clone, Debug, Parser)]
 pub struct RunRead {
@@ -20,11 +19,10 @@ pub struct RunRead {
 
 impl RunRead {
     pub fn run(&self, c: WxArguments) -> anyhow::Result<()> {
-        let mut wechat_info = WxScanner::default();
-        wechat_info.open_wechat_process_with_out_info(&c.process_id, &c.process_name, &c.module_name)?;
+        let wechat_info = c.open_memory_with_out_info()?;
         let data = wechat_info.read_memory(self.index, self.len, self.absolute_address)?;
         if let Some(encode) = self.encode.as_ref() {
-            println!("{}", u8_to_string(&data, &encode)?);
+            println!("{}", u8_to_string(&data, encode)?);
         }
         else {
             println!("{:?}", &data);
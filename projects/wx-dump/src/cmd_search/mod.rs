@@ -1,6 +1,6 @@
 use crate::{WxArguments, utils::string_to_u8_vec};
 use clap::Parser;
-use wx_core::WxScanner;
+use wx_core::{WxMemory, WxScanner};
 
 #[derive(Clone, Debug, Parser)]
 pub struct RunSearch {
@@ -28,9 +28,19 @@ pub struct RunSearch {
 }
 impl RunSearch {
     pub fn run(&self, c: WxArguments) -> anyhow::Result<()> {
+        let data = string_to_u8_vec(&self.str, &self.encode)?;
+        if c.dump_file.is_some() {
+            let dump = c.open_memory_with_out_info()?;
+            if self.from_all_data || self.from_all_modules {
+                println!("{:?}", dump.search_in_all_regions(&data)?);
+            }
+            else {
+                println!("{:?}", dump.memory_search(&data, self.real_addr)?);
+            }
+            return anyhow::Ok(());
+        }
         let mut wechat_info = WxScanner::default();
         wechat_info.open_wechat_process_with_out_info(&c.process_id, &c.process_name, &c.module_name)?;
-        let data = string_to_u8_vec(&self.str, &self.encode)?;
         if self.from_all_data {
             wechat_info.search_in_all_wechat_data(&data, self.real_addr, self.show_no_found_info, self.show_error_info)?;
         }
@@ -0,0 +1,51 @@
+use crate::{WxArguments, utils::string_to_u8_vec};
+use clap::Parser;
+use std::path::PathBuf;
+use wx_core::{WxDecryptor, helpers::read_database};
+
+#[derive(Clone, Debug, Parser)]
+pub struct RunVerifyKey {
+    /// 需要校验的 key
+    pub key: String,
+    /// key的编码格式，可选值：[hex,base64,string,u64be,u64le,...]
+    #[arg(short = 'd', long, default_value = "hex")]
+    pub encode: String,
+    /// 加密的数据库或者数据库所在的文件夹，不填写时校验所有账号的 Msg 文件夹
+    #[arg(long, value_name = "数据库")]
+    pub db: Option<String>,
+}
+
+impl RunVerifyKey {
+    pub async fn run(self, c: WxArguments) -> anyhow::Result<()> {
+        let key_vec = string_to_u8_vec(&self.key, &self.encode)?;
+        let key: [u8; 32] = match key_vec.get(0..32) {
+            Some(s) => s.try_into()?,
+            None => return Err(anyhow::anyhow!("请输入正确的key")),
+        };
+        let roots = match self.db.as_ref() {
+            Some(s) => vec![PathBuf::from(s)],
+            None => read_database(&c.wechat_path).await?.into_values().collect(),
+        };
+        let mut opened = 0;
+        for root in roots {
+            let results = match root.is_file() {
+                true => vec![(root.clone(), WxDecryptor::verify_key(&root, &key))],
+                false => WxDecryptor::verify_key_tree(&root, &key).into_iter().map(|(f, r)| (root.join(f), r)).collect(),
+            };
+            for (file, result) in results {
+                match result {
+                    Ok(Some(profile)) => {
+                        opened += 1;
+                        println!("{}: 可以打开, {:?}", file.display(), profile)
+                    }
+                    Ok(None) => println!("{}: 秘钥错误", file.display()),
+                    Err(e) => println!("{}: {}", file.display(), e),
+                }
+            }
+        }
+        match opened {
+            0 => Err(anyhow::anyhow!("秘钥无法打开任何数据库")),
+            _ => anyhow::Ok(()),
+        }
+    }
+}
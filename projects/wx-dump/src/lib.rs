@@ -1,25 +1,31 @@
 use anyhow::Ok;
 use std::env::current_dir;
 
+mod cmd_accounts;
 mod cmd_copy;
 mod cmd_decrypt;
 mod cmd_export;
+mod cmd_find_key;
 mod cmd_info;
+mod cmd_offsets;
 mod cmd_read;
 mod cmd_read_memory;
 mod cmd_search;
+mod cmd_verify_key;
 mod cmd_wx_path;
 
 mod utils;
 
+use crate::utils::{print_report, progress_printer};
 const DEFAULT_SAVE_DIR: &str = "target";
 
 pub use crate::{
-    cmd_copy::RunCopy, cmd_decrypt::RunDecrypt, cmd_export::RunExport, cmd_info::RunInfo, cmd_read::RunRead,
-    cmd_search::RunSearch,
+    cmd_accounts::RunAccounts, cmd_copy::RunCopy, cmd_decrypt::RunDecrypt, cmd_export::RunExport, cmd_find_key::RunFindKey,
+    cmd_info::RunInfo, cmd_offsets::RunOffsets, cmd_read::RunRead, cmd_search::RunSearch, cmd_verify_key::RunVerifyKey,
 };
 use clap::{Parser, Subcommand};
-use wx_core::{WxDecryptor, WxScanner, helpers::read_database};
+use std::path::Path;
+use wx_core::{WxDecryptor, WxMemory, WxMemoryDump, WxScanner, helpers::read_database};
 
 /// 微信聊天记录导出工具
 #[derive(Parser, Debug)]
@@ -51,6 +57,48 @@ pub struct WxArguments {
     /// 指定模块名
     #[arg(long, default_value = "WeChatWin.dll")]
     module_name: String,
+    /// 指定内存转储文件, 支持 minidump 和 ELF core, 指定后不再读取微信进程
+    #[arg(long, value_name = "转储文件")]
+    dump_file: Option<String>,
+    /// 解密报告的输出格式，可选值：[table, json]
+    #[arg(long, default_value = "table")]
+    report: String,
+    /// 增量解密，跳过没有变化的数据库，只重写变化的页
+    #[arg(long, default_value = "false")]
+    incremental: bool,
+}
+
+impl WxArguments {
+    /// 打开微信进程或内存转储文件, 并读取个人数据
+    pub fn open_memory(&self) -> anyhow::Result<Box<dyn WxMemory>> {
+        match self.dump_file.as_ref() {
+            Some(dump_file) => {
+                let mut dump = WxMemoryDump::default();
+                dump.open_wechat_dump(Path::new(dump_file), &self.offset_map, &self.module_name)?;
+                Ok(Box::new(dump))
+            }
+            None => {
+                let mut scanner = WxScanner::default();
+                scanner.open_wechat_process(&self.offset_map, &self.process_id, &self.process_name, &self.module_name)?;
+                Ok(Box::new(scanner))
+            }
+        }
+    }
+    /// 打开微信进程或内存转储文件, 不读取个人数据
+    pub fn open_memory_with_out_info(&self) -> anyhow::Result<Box<dyn WxMemory>> {
+        match self.dump_file.as_ref() {
+            Some(dump_file) => {
+                let mut dump = WxMemoryDump::default();
+                dump.open_wechat_dump_with_out_info(Path::new(dump_file), &self.module_name)?;
+                Ok(Box::new(dump))
+            }
+            None => {
+                let mut scanner = WxScanner::default();
+                scanner.open_wechat_process_with_out_info(&self.process_id, &self.process_name, &self.module_name)?;
+                Ok(Box::new(scanner))
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug, Subcommand)]
@@ -65,8 +113,16 @@ pub enum WxCommands {
     Search(RunSearch),
     /// 从内存中指定的位置搜索信息
     Read(RunRead),
-    /// 从内存中指定的位置搜索信息
+    /// 复制正在使用的数据库，生成可以离线解密的快照
     Copy(RunCopy),
+    /// 不依赖偏移量，在内存中寻找能够解密数据库的秘钥
+    FindKey(RunFindKey),
+    /// 根据已知的个人数据推导当前版本的偏移量，并写入偏移量文件
+    Offsets(RunOffsets),
+    /// 校验 key 能否打开数据库，只检查第一页的 hmac
+    VerifyKey(RunVerifyKey),
+    /// 列出微信文件夹中的账号和数据库
+    Accounts(RunAccounts),
 }
 
 impl WxDump {
@@ -74,33 +130,40 @@ impl WxDump {
         match self.cmds {
             Some(subs) => match subs {
                 WxCommands::Info(cmd) => cmd.run(self.args),
-                WxCommands::Decrypt(cmd) => cmd.run(self.args),
+                WxCommands::Decrypt(cmd) => cmd.run(self.args).await,
                 WxCommands::Search(cmd) => cmd.run(self.args),
                 WxCommands::Read(cmd) => cmd.run(self.args),
                 WxCommands::Export(cmd) => cmd.run(self.args).await,
-                WxCommands::Copy(cmd) => cmd.run(self.args),
+                WxCommands::Copy(cmd) => cmd.run(self.args).await,
+                WxCommands::FindKey(cmd) => cmd.run(self.args),
+                WxCommands::Offsets(cmd) => cmd.run(self.args),
+                WxCommands::VerifyKey(cmd) => cmd.run(self.args).await,
+                WxCommands::Accounts(cmd) => cmd.run(self.args),
             },
             None => Self::run_auto(self.args).await,
         }
     }
     pub async fn run_auto(c: WxArguments) -> anyhow::Result<()> {
-        let mut wechat_info = WxScanner::default();
-        wechat_info.open_wechat_process(&c.offset_map, &c.process_id, &c.process_name, &c.module_name)?;
-        println!("{:#?}", wechat_info.profile);
+        let wechat_info = c.open_memory()?;
+        println!("{:#?}", wechat_info.profile());
         let data = read_database(&c.wechat_path).await.unwrap();
         for (user, path) in data.iter() {
             let output_path = current_dir()?.join(DEFAULT_SAVE_DIR).join(user);
+            let (sender, printer) = progress_printer();
             let decryptor = WxDecryptor {
                 source_path: path.to_path_buf(),
                 output_path,
-                key: wechat_info.profile.aes256.to_owned(),
-                need_check_hmac: false,
+                key: wechat_info.profile().aes256.to_owned(),
+                progress: Some(sender),
+                incremental: c.incremental,
+                ..Default::default()
             };
-            match decryptor.decrypt().await {
-                Err(e) => {
-                    println!("{e}");
-                }
-                _ => {}
+            let report = decryptor.decrypt().await;
+            drop(decryptor);
+            let _ = printer.join();
+            match report {
+                Result::Ok(o) => print_report(&o, &c.report)?,
+                Err(e) => println!("{e}"),
             }
         }
         Ok(())
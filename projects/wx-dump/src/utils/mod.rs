@@ -1,8 +1,12 @@
 use base64::Engine;
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
-use wx_core::{WxError, WxResult};
+use std::{
+    sync::mpsc::{Sender, channel},
+    thread::JoinHandle,
+};
+use wx_core::{WxDecryptProgress, WxDecryptReport, WxDecryptStatus, WxError, WxResult};
 
-pub fn string_to_u8_vec(data: &String, encode: &String) -> WxResult<Vec<u8>> {
+pub fn string_to_u8_vec(data: &str, encode: &str) -> WxResult<Vec<u8>> {
     let mut buffer = vec![];
     match encode.to_ascii_lowercase().as_str() {
         "hex" => {
@@ -13,7 +17,7 @@ pub fn string_to_u8_vec(data: &String, encode: &String) -> WxResult<Vec<u8>> {
             Ok(buffer)
         }
         "base64" => {
-            buffer = base64::engine::general_purpose::STANDARD_NO_PAD.decode(&data)?;
+            buffer = base64::engine::general_purpose::STANDARD_NO_PAD.decode(data)?;
             Ok(buffer)
         }
         "string" => {
@@ -72,10 +76,10 @@ pub fn string_to_u8_vec(data: &String, encode: &String) -> WxResult<Vec<u8>> {
     }
 }
 
-pub fn u8_to_string(data: &[u8], encode: &String) -> WxResult<String> {
+pub fn u8_to_string(data: &[u8], encode: &str) -> WxResult<String> {
     match encode.to_ascii_lowercase().as_str() {
         "hex" => Ok(to_hex(data)),
-        "base64" => Ok(base64::engine::general_purpose::STANDARD_NO_PAD.encode(&data)),
+        "base64" => Ok(base64::engine::general_purpose::STANDARD_NO_PAD.encode(data)),
         "string" => Ok(String::from_utf8(data.split(|e| *e == 0).next().unwrap().to_vec())?),
         "u64be" => {
             let mut cur = std::io::Cursor::new(data);
@@ -139,3 +143,92 @@ pub fn to_hex(data: impl AsRef<[u8]>) -> String {
 }
 
 pub static HEX_TABLE: [char; 16] = ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'A', 'B', 'C', 'D', 'E', 'F'];
+
+/// 在终端上显示解密进度, 发送端全部释放后线程结束
+pub fn progress_printer() -> (Sender<WxDecryptProgress>, JoinHandle<()>) {
+    let (sender, receiver) = channel::<WxDecryptProgress>();
+    let handle = std::thread::spawn(move || {
+        for progress in receiver {
+            let percent = match progress.bytes_total {
+                0 => 100,
+                total => progress.bytes_done * 100 / total,
+            };
+            eprint!("\r解密中: {} {:>3}%", progress.file.display(), percent);
+            if progress.bytes_done >= progress.bytes_total {
+                eprintln!();
+            }
+        }
+    });
+    (sender, handle)
+}
+
+/// 按指定格式打印解密报告, 支持 `table` 和 `json`
+pub fn print_report(report: &WxDecryptReport, format: &str) -> WxResult<()> {
+    match format.to_ascii_lowercase().as_str() {
+        "table" => {
+            println!("{:<40} {:>8} {:>8} {:>8} {:>10}  状态", "文件", "页数", "写入页数", "WAL 帧数", "耗时");
+            for file in &report.files {
+                println!(
+                    "{:<40} {:>8} {:>8} {:>8} {:>9.2}s  {}",
+                    file.file.display(),
+                    file.pages,
+                    file.pages_written,
+                    file.wal_frames,
+                    file.elapsed.as_secs_f64(),
+                    file.status
+                );
+                for warning in &file.warnings {
+                    println!("    警告: {}", warning);
+                }
+            }
+            println!(
+                "共 {} 个文件, 成功 {} 个, 未修改 {} 个, 未加密 {} 个, 失败 {} 个, 耗时 {:.2}s",
+                report.files.len(),
+                report.count(|s| matches!(s, WxDecryptStatus::Decrypted)),
+                report.count(|s| matches!(s, WxDecryptStatus::Unchanged)),
+                report.count(|s| matches!(s, WxDecryptStatus::NotEncrypted)),
+                report.count(|s| !s.is_success()),
+                report.elapsed.as_secs_f64()
+            );
+        }
+        "json" => {
+            let files: Vec<_> = report
+                .files
+                .iter()
+                .map(|file| {
+                    let (status, page, message) = match &file.status {
+                        WxDecryptStatus::Decrypted => ("decrypted", None, None),
+                        WxDecryptStatus::NotEncrypted => ("not_encrypted", None, None),
+                        WxDecryptStatus::Unchanged => ("unchanged", None, None),
+                        WxDecryptStatus::Salvaged => ("salvaged", None, None),
+                        WxDecryptStatus::WrongKey => ("wrong_key", None, None),
+                        WxDecryptStatus::HmacFailure { page } => ("hmac_failure", Some(*page), None),
+                        WxDecryptStatus::IoError { message } => ("io_error", None, Some(message)),
+                        WxDecryptStatus::Failed { message } => ("failed", None, Some(message)),
+                    };
+                    serde_json::json!({
+                        "file": file.file.to_string_lossy(),
+                        "status": status,
+                        "page": page,
+                        "message": message,
+                        "pages": file.pages,
+                        "pages_written": file.pages_written,
+                        "wal_frames": file.wal_frames,
+                        "zeroed_pages": file.zeroed_pages,
+                        "dropped_bytes": file.dropped_bytes,
+                        "elapsed_ms": file.elapsed.as_millis() as u64,
+                        "warnings": file.warnings,
+                    })
+                })
+                .collect();
+            let json = serde_json::json!({
+                "success": report.is_success(),
+                "elapsed_ms": report.elapsed.as_millis() as u64,
+                "files": files,
+            });
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        }
+        _ => Err(WxError::custom("错误的报告格式"))?,
+    }
+    Ok(())
+}
@@ -1,8 +1,9 @@
+use clap::Parser;
 use std::{
-    env::{current_dir, set_current_dir},
+    env::set_current_dir,
     path::{Path, PathBuf},
 };
-use wx_dump::{RunExport, WxArguments, WxDump};
+use wx_dump::{RunCopy, RunExport, WxArguments};
 
 #[test]
 fn ready() {
@@ -18,6 +19,22 @@ pub async fn test_export() -> anyhow::Result<()> {
     run.run(WxArguments::default()).await
 }
 
+/// 数据库一直无法复制时, 用完重试次数后返回错误
+#[tokio::test]
+async fn copy_retries_exhausted() {
+    let root = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("copy_exhausted");
+    let _ = std::fs::remove_dir_all(&root);
+    let msg = root.join("WeChat Files/wxid_test/Msg");
+    std::fs::create_dir_all(msg.join("MicroMsg.db-wal")).unwrap();
+    std::fs::write(msg.join("MicroMsg.db"), vec![1u8; 4096]).unwrap();
+    let wechat_path = root.join("WeChat Files");
+    let args = WxArguments::parse_from(["wxdump", "--wechat-path", wechat_path.to_str().unwrap()]);
+    let backup = root.join("backup");
+    let run = RunCopy::parse_from(["copy", "--output", backup.to_str().unwrap(), "--retries", "1", "--retry-delay", "0"]);
+    let error = run.run(args).await.unwrap_err();
+    assert!(error.to_string().contains("1 个文件没有得到一致的快照"), "{}", error);
+}
+
 fn set_workspace_dir() -> std::io::Result<()> {
     let package_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
     match package_dir.parent().and_then(|s| s.parent()) {